@@ -33,6 +33,8 @@ pub struct CUstream_ {
 }
 pub type CUstream = *mut CUstream_;
 
+pub const CU_STREAM_NON_BLOCKING: ::std::os::raw::c_uint = 1;
+
 pub type CUdeviceptr = ::std::os::raw::c_ulonglong;
 
 pub type CUdevice = ::std::os::raw::c_int;
@@ -54,6 +56,10 @@ impl Cuda {
 impl Cuda {
     impl_library! {
         "system" fn cuInit(Flags: ::std::os::raw::c_uint) -> CUresult;
+        "system" fn cuDeviceGetCount(count: *mut ::std::os::raw::c_int) -> CUresult;
+        "system" fn cuDeviceGet(device: *mut CUdevice, ordinal: ::std::os::raw::c_int) -> CUresult;
+        "system" fn cuGetErrorName(error: CUresult, pStr: *mut *const ::std::os::raw::c_char) -> CUresult;
+        "system" fn cuGetErrorString(error: CUresult, pStr: *mut *const ::std::os::raw::c_char) -> CUresult;
         "system" fn cuCtxCreate_v2(
             pctx: *mut CUcontext,
             flags: ::std::os::raw::c_uint,
@@ -69,10 +75,11 @@ impl Cuda {
             name: *const ::std::os::raw::c_char,
         ) -> CUresult;
         "system" fn cuMemAlloc_v2(dptr: *mut CUdeviceptr, bytesize: usize) -> CUresult;
-        "system" fn cuMemcpyHtoD_v2(
+        "system" fn cuMemcpyHtoDAsync_v2(
             dstDevice: CUdeviceptr,
             srcHost: *const ::std::os::raw::c_void,
             ByteCount: usize,
+            hStream: CUstream,
         ) -> CUresult;
         //"system" fn cuMemsetD8_v2(dstDevice: CUdeviceptr, uc: ::std::os::raw::c_uchar, N: usize)
         //    -> CUresult;
@@ -89,13 +96,18 @@ impl Cuda {
             kernelParams: *mut *mut ::std::os::raw::c_void,
             extra: *mut *mut ::std::os::raw::c_void,
         ) -> CUresult;
-        "system" fn cuMemcpyDtoH_v2(
+        "system" fn cuMemcpyDtoHAsync_v2(
             dstHost: *mut ::std::os::raw::c_void,
             srcDevice: CUdeviceptr,
             ByteCount: usize,
+            hStream: CUstream,
         ) -> CUresult;
+        "system" fn cuStreamCreate(phStream: *mut CUstream, Flags: ::std::os::raw::c_uint) -> CUresult;
+        "system" fn cuStreamDestroy_v2(hStream: CUstream) -> CUresult;
         "system" fn cuStreamSynchronize(hStream: CUstream) -> CUresult;
         "system" fn cuMemFree_v2(dptr: CUdeviceptr) -> CUresult;
+        "system" fn cuMemAllocHost_v2(pp: *mut *mut ::std::os::raw::c_void, bytesize: usize) -> CUresult;
+        "system" fn cuMemFreeHost(p: *mut ::std::os::raw::c_void) -> CUresult;
         "system" fn cuModuleUnload(hmod: CUmodule) -> CUresult;
         //"system" fn cuCtxDestroy_v2(ctx: CUcontext) -> CUresult;
         "system" fn cuMemGetInfo_v2(free: *mut usize, total: *mut usize) -> CUresult;
@@ -0,0 +1,227 @@
+use std::{
+    ffi::{c_char, c_int, c_void, CStr},
+    fmt,
+    num::NonZeroU32,
+    ptr,
+};
+
+use crate::cuda::{
+    CUdeviceptr, CUfunction, CUmodule, CUresult, CUstream, Cuda, CU_STREAM_NON_BLOCKING,
+};
+
+/// Error reported by a failing `Driver` call.
+#[derive(Debug, Clone)]
+pub struct DriverFailure {
+    pub code: u32,
+    pub name: String,
+    pub description: String,
+}
+
+impl fmt::Display for DriverFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.name, self.code, self.description)
+    }
+}
+
+pub type DriverResult<T> = Result<T, DriverFailure>;
+
+/// Subset of the CUDA driver API used by the test runner.
+///
+/// Implemented by `Cuda` for real drivers. Handles are opaque to the runner, so other
+/// implementations are free to use them for their own bookkeeping.
+/// Implementations are shared between threads, each thread running tests on a different device.
+pub trait Driver: Sync {
+    /// Number of devices that can be passed to `init`
+    fn device_count(&self) -> DriverResult<usize>;
+    /// Initialize the driver and make a context on `device` current on the calling thread
+    fn init(&self, device: usize) -> DriverResult<()>;
+    /// Load a module from PTX, failures are reported as compilation failures of the test
+    fn load_module(&self, image: &CStr) -> DriverResult<CUmodule>;
+    fn unload_module(&self, module: CUmodule) -> DriverResult<()>;
+    fn get_function(&self, module: CUmodule, name: &CStr) -> DriverResult<CUfunction>;
+    /// Free and total device memory in bytes
+    fn mem_info(&self) -> DriverResult<(usize, usize)>;
+    fn mem_alloc(&self, size: usize) -> DriverResult<CUdeviceptr>;
+    fn mem_free(&self, ptr: CUdeviceptr) -> DriverResult<()>;
+    /// Allocate page-locked host memory, which copies can use asynchronously
+    fn mem_alloc_host(&self, size: usize) -> DriverResult<*mut u8>;
+    /// # Safety
+    /// `ptr` must have been returned by `mem_alloc_host` and not freed yet
+    unsafe fn mem_free_host(&self, ptr: *mut u8) -> DriverResult<()>;
+    fn stream_create(&self) -> DriverResult<CUstream>;
+    fn stream_destroy(&self, stream: CUstream) -> DriverResult<()>;
+    fn stream_synchronize(&self, stream: CUstream) -> DriverResult<()>;
+    /// # Safety
+    /// `src` must be valid for reads of `size` bytes until `stream` is synchronized
+    unsafe fn memcpy_htod_async(
+        &self,
+        dst: CUdeviceptr,
+        src: *const u8,
+        size: usize,
+        stream: CUstream,
+    ) -> DriverResult<()>;
+    /// # Safety
+    /// `dst` must be valid for writes of `size` bytes until `stream` is synchronized
+    unsafe fn memcpy_dtoh_async(
+        &self,
+        dst: *mut u8,
+        src: CUdeviceptr,
+        size: usize,
+        stream: CUstream,
+    ) -> DriverResult<()>;
+    /// Launch `function` on a one-dimensional grid, every kernel parameter is a device pointer
+    fn launch_kernel(
+        &self,
+        function: CUfunction,
+        grid_size: u32,
+        block_size: u32,
+        stream: CUstream,
+        params: &[CUdeviceptr],
+    ) -> DriverResult<()>;
+}
+
+impl Cuda {
+    fn check(&self, result: CUresult) -> DriverResult<()> {
+        fn error_string(get: impl FnOnce(*mut *const c_char) -> CUresult) -> String {
+            let mut text = ptr::null();
+            match get(&mut text) {
+                Ok(()) if !text.is_null() => unsafe { CStr::from_ptr(text) }
+                    .to_string_lossy()
+                    .into_owned(),
+                _ => "UNKNOWN".to_string(),
+            }
+        }
+
+        let code: NonZeroU32 = match result {
+            Ok(()) => return Ok(()),
+            Err(code) => code,
+        };
+        Err(DriverFailure {
+            code: code.get(),
+            name: error_string(|text| unsafe { self.cuGetErrorName(Err(code), text) }),
+            description: error_string(|text| unsafe { self.cuGetErrorString(Err(code), text) }),
+        })
+    }
+}
+
+impl Driver for Cuda {
+    fn device_count(&self) -> DriverResult<usize> {
+        self.check(unsafe { self.cuInit(0) })?;
+        let mut count = 0;
+        self.check(unsafe { self.cuDeviceGetCount(&mut count) })?;
+        Ok(count as usize)
+    }
+
+    fn init(&self, device: usize) -> DriverResult<()> {
+        self.check(unsafe { self.cuInit(0) })?;
+        let mut dev = 0;
+        self.check(unsafe { self.cuDeviceGet(&mut dev, device as c_int) })?;
+        // Creating a context also makes it current on the calling thread
+        let mut ctx = ptr::null_mut();
+        self.check(unsafe { self.cuCtxCreate_v2(&mut ctx, 0, dev) })
+    }
+
+    fn load_module(&self, image: &CStr) -> DriverResult<CUmodule> {
+        let mut module = ptr::null_mut();
+        self.check(unsafe { self.cuModuleLoadData(&mut module, image.as_ptr().cast()) })?;
+        Ok(module)
+    }
+
+    fn unload_module(&self, module: CUmodule) -> DriverResult<()> {
+        self.check(unsafe { self.cuModuleUnload(module) })
+    }
+
+    fn get_function(&self, module: CUmodule, name: &CStr) -> DriverResult<CUfunction> {
+        let mut function = ptr::null_mut();
+        self.check(unsafe { self.cuModuleGetFunction(&mut function, module, name.as_ptr()) })?;
+        Ok(function)
+    }
+
+    fn mem_info(&self) -> DriverResult<(usize, usize)> {
+        let mut free = 0;
+        let mut total = 0;
+        self.check(unsafe { self.cuMemGetInfo_v2(&mut free, &mut total) })?;
+        Ok((free, total))
+    }
+
+    fn mem_alloc(&self, size: usize) -> DriverResult<CUdeviceptr> {
+        let mut ptr = 0;
+        self.check(unsafe { self.cuMemAlloc_v2(&mut ptr, size) })?;
+        Ok(ptr)
+    }
+
+    fn mem_free(&self, ptr: CUdeviceptr) -> DriverResult<()> {
+        self.check(unsafe { self.cuMemFree_v2(ptr) })
+    }
+
+    fn mem_alloc_host(&self, size: usize) -> DriverResult<*mut u8> {
+        let mut ptr = ptr::null_mut();
+        self.check(unsafe { self.cuMemAllocHost_v2(&mut ptr, size) })?;
+        Ok(ptr.cast())
+    }
+
+    unsafe fn mem_free_host(&self, ptr: *mut u8) -> DriverResult<()> {
+        self.check(self.cuMemFreeHost(ptr.cast()))
+    }
+
+    fn stream_create(&self) -> DriverResult<CUstream> {
+        let mut stream = ptr::null_mut();
+        self.check(unsafe { self.cuStreamCreate(&mut stream, CU_STREAM_NON_BLOCKING) })?;
+        Ok(stream)
+    }
+
+    fn stream_destroy(&self, stream: CUstream) -> DriverResult<()> {
+        self.check(unsafe { self.cuStreamDestroy_v2(stream) })
+    }
+
+    fn stream_synchronize(&self, stream: CUstream) -> DriverResult<()> {
+        self.check(unsafe { self.cuStreamSynchronize(stream) })
+    }
+
+    unsafe fn memcpy_htod_async(
+        &self,
+        dst: CUdeviceptr,
+        src: *const u8,
+        size: usize,
+        stream: CUstream,
+    ) -> DriverResult<()> {
+        self.check(self.cuMemcpyHtoDAsync_v2(dst, src.cast(), size, stream))
+    }
+
+    unsafe fn memcpy_dtoh_async(
+        &self,
+        dst: *mut u8,
+        src: CUdeviceptr,
+        size: usize,
+        stream: CUstream,
+    ) -> DriverResult<()> {
+        self.check(self.cuMemcpyDtoHAsync_v2(dst.cast(), src, size, stream))
+    }
+
+    fn launch_kernel(
+        &self,
+        function: CUfunction,
+        grid_size: u32,
+        block_size: u32,
+        stream: CUstream,
+        params: &[CUdeviceptr],
+    ) -> DriverResult<()> {
+        // The driver copies parameter values during the call, they only need to outlive it
+        let mut args: Vec<*mut c_void> = params.iter().map(|p| p as *const _ as _).collect();
+        self.check(unsafe {
+            self.cuLaunchKernel(
+                function,
+                grid_size,
+                1,
+                1,
+                block_size,
+                1,
+                1,
+                0,
+                stream,
+                args.as_mut_ptr() as _,
+                ptr::null_mut(),
+            )
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use std::{fs, path::Path};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::report::{TestRecord, TestStatus};
+
+/// What is known about tests matching an entry of the manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expectation {
+    /// The test is known to fail, its failures are not counted
+    Xfail,
+    /// The test is not executed at all, e.g. because it hangs
+    Skip,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    test: String,
+    status: Expectation,
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    expect: Vec<Entry>,
+}
+
+/// Known failures read from a TOML manifest given with `--expectations`:
+///
+/// ```toml
+/// [[expect]]
+/// test = "^sin_approx_"
+/// status = "xfail"
+/// reason = "approximate transcendentals are less precise than on NVIDIA GPUs"
+/// ```
+///
+/// `test` is a regex matched against test names like `--filter`, the first matching entry wins.
+#[derive(Debug, Default)]
+pub struct Expectations {
+    entries: Vec<(Regex, Expectation, String)>,
+}
+
+impl Expectations {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let manifest: Manifest =
+            toml::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))?;
+        let entries = manifest
+            .expect
+            .into_iter()
+            .map(|entry| {
+                let regex = Regex::new(&entry.test).map_err(|err| {
+                    format!("{}: invalid regex {}: {err}", path.display(), entry.test)
+                })?;
+                Ok((regex, entry.status, entry.reason))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Expectations { entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, test: &str) -> Option<(Expectation, &str)> {
+        self.entries
+            .iter()
+            .find(|(regex, _, _)| regex.is_match(test))
+            .map(|(_, expectation, reason)| (*expectation, reason.as_str()))
+    }
+
+    /// Record of a test that was not executed because the manifest says to skip it.
+    pub fn skipped(&self, test: &str) -> Option<TestRecord> {
+        match self.get(test)? {
+            (Expectation::Skip, reason) => Some(TestRecord {
+                name: test.to_string(),
+                status: TestStatus::Skip,
+                duration_secs: 0.0,
+                error: None,
+                reason: Some(reason.to_string()),
+            }),
+            (Expectation::Xfail, _) => None,
+        }
+    }
+
+    /// Turn failures of tests expected to fail into expected failures and their passes into
+    /// unexpected passes.
+    pub fn apply(&self, mut record: TestRecord) -> TestRecord {
+        if let Some((Expectation::Xfail, reason)) = self.get(&record.name) {
+            record.status = match record.status {
+                TestStatus::Ok => TestStatus::Xpass,
+                TestStatus::Fail => TestStatus::Xfail,
+                status => status,
+            };
+            record.reason = Some(reason.to_string());
+        }
+        record
+    }
+}
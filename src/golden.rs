@@ -0,0 +1,246 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+const MAGIC: &[u8; 8] = b"PTXGOLD1";
+
+/// Where the GPU outputs of a single test are recorded to or compared against.
+#[derive(Debug, Clone)]
+pub enum Golden {
+    Record(PathBuf),
+    Compare(PathBuf),
+}
+
+/// Path of the file holding the recorded outputs of a test in `dir`.
+pub fn golden_path(dir: &Path, test: &str) -> PathBuf {
+    dir.join(format!("{test}.golden.gz"))
+}
+
+/// Identifies the cases a golden file was recorded from.
+///
+/// Outputs are only comparable if the test generated the same inputs and compiled the same
+/// source, so all of it is stored in the file header and checked before comparing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoldenKey {
+    /// Seed of the generated inputs, `None` for tests running over a range of values
+    pub seed: Option<u64>,
+    /// Hash of the full text loaded for the test, see `TestContext::golden_source`
+    pub source_hash: u64,
+    pub total_cases: u64,
+    pub output_size: u64,
+}
+
+impl GoldenKey {
+    pub fn new(source: &str, seed: Option<u64>, total_cases: usize, output_size: usize) -> Self {
+        GoldenKey {
+            seed,
+            source_hash: source_hash(source),
+            total_cases: total_cases as u64,
+            output_size: output_size as u64,
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.seed.is_some() as u8])?;
+        for value in [
+            self.seed.unwrap_or(0),
+            self.source_hash,
+            self.total_cases,
+            self.output_size,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a golden output file",
+            ));
+        }
+        let mut has_seed = [0u8; 1];
+        reader.read_exact(&mut has_seed)?;
+        let mut values = [0u64; 4];
+        for value in values.iter_mut() {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes)?;
+            *value = u64::from_le_bytes(bytes);
+        }
+        let [seed, source_hash, total_cases, output_size] = values;
+        Ok(GoldenKey {
+            seed: (has_seed[0] != 0).then_some(seed),
+            source_hash,
+            total_cases,
+            output_size,
+        })
+    }
+}
+
+/// FNV-1a hash of the test source, stable across platforms and compiler versions.
+fn source_hash(source: &str) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in source.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Outputs are written in the order of the cases, independent of how they were split into
+/// batches, so recording the same test twice produces identical files.
+pub struct GoldenWriter {
+    encoder: GzEncoder<BufWriter<File>>,
+}
+
+impl GoldenWriter {
+    pub fn create(path: &Path, key: &GoldenKey) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        key.write(&mut encoder)?;
+        Ok(GoldenWriter { encoder })
+    }
+
+    pub fn write(&mut self, outputs: &[u8]) -> io::Result<()> {
+        self.encoder.write_all(outputs)
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.finish()?.flush()
+    }
+}
+
+pub struct GoldenReader {
+    decoder: GzDecoder<BufReader<File>>,
+}
+
+impl GoldenReader {
+    /// Open a golden file, failing if it was recorded from different cases than `key`.
+    pub fn open(path: &Path, key: &GoldenKey) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut decoder = GzDecoder::new(BufReader::new(file));
+        let recorded =
+            GoldenKey::read(&mut decoder).map_err(|err| format!("{}: {err}", path.display()))?;
+        if recorded.seed != key.seed {
+            return Err(format!(
+                "{} was recorded with seed {:?}, expected {:?}",
+                path.display(),
+                recorded.seed,
+                key.seed
+            ));
+        }
+        if recorded.source_hash != key.source_hash {
+            return Err(format!(
+                "{} was recorded from a different source, e.g. for another target, PTX \
+                 version or NVRTC options",
+                path.display()
+            ));
+        }
+        if recorded != *key {
+            return Err(format!(
+                "{} holds {} cases of {} bytes, expected {} cases of {} bytes",
+                path.display(),
+                recorded.total_cases,
+                recorded.output_size,
+                key.total_cases,
+                key.output_size
+            ));
+        }
+        Ok(GoldenReader { decoder })
+    }
+
+    /// Read the outputs of the next `len` bytes worth of cases.
+    pub fn read(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut outputs = vec![0u8; len];
+        self.decoder.read_exact(&mut outputs)?;
+        Ok(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        metadata::PtxVersion,
+        test::TestPtx,
+        testcase::{golden_source, Target},
+    };
+
+    struct CopyTest;
+
+    impl TestPtx for CopyTest {
+        fn body(&self) -> String {
+            "st.global.u32 [out], in;".to_string()
+        }
+
+        fn args(&self) -> Vec<&str> {
+            vec!["in", "out"]
+        }
+    }
+
+    fn target(ptx_version: PtxVersion, sm: &str) -> Target {
+        Target {
+            ptx_version,
+            sm: sm.to_string(),
+            nvrtc_arch: sm.to_string(),
+            nvrtc_options: Vec::new(),
+        }
+    }
+
+    fn key(target: &Target, nvrtc: bool) -> GoldenKey {
+        GoldenKey::new(&golden_source(&CopyTest, target, nvrtc), Some(1), 128, 4)
+    }
+
+    fn record(name: &str, key: &GoldenKey) -> PathBuf {
+        let path = golden_path(&std::env::temp_dir().join("ptx_tests_golden"), name);
+        let mut writer = GoldenWriter::create(&path, key).unwrap();
+        writer.write(&[0; 128 * 4]).unwrap();
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn same_source_is_accepted() {
+        let key = key(&target(PtxVersion(7, 8), "sm_90"), false);
+        let path = record("same_source", &key);
+        let mut reader = GoldenReader::open(&path, &key).unwrap();
+        assert_eq!(reader.read(128 * 4).unwrap(), vec![0; 128 * 4]);
+    }
+
+    #[test]
+    fn other_target_is_rejected() {
+        let recorded = key(&target(PtxVersion(7, 8), "sm_90"), false);
+        let path = record("other_target", &recorded);
+        for other in [
+            target(PtxVersion(6, 5), "sm_70"),
+            target(PtxVersion(7, 8), "sm_70"),
+            target(PtxVersion(6, 5), "sm_90"),
+        ] {
+            let err = GoldenReader::open(&path, &key(&other, false))
+                .err()
+                .unwrap();
+            assert!(err.contains("different source"), "{err}");
+        }
+    }
+
+    #[test]
+    fn nvrtc_is_rejected_without_nvrtc() {
+        let target = target(PtxVersion(7, 8), "sm_90");
+        let path = record("nvrtc", &key(&target, true));
+        assert!(GoldenReader::open(&path, &key(&target, false)).is_err());
+        let mut optimized = target.clone();
+        optimized.nvrtc_options.push("-O0".to_string());
+        assert!(GoldenReader::open(&path, &key(&optimized, true)).is_err());
+    }
+}
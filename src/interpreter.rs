@@ -0,0 +1,1455 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CStr,
+    fmt, ptr,
+    sync::Mutex,
+};
+
+use crate::{
+    cuda::{CUdeviceptr, CUfunction, CUmodule, CUstream},
+    driver::{Driver, DriverFailure, DriverResult},
+};
+
+/// Device memory reported by `mem_info`, keeps batches small enough for a laptop
+const MEMORY_SIZE: usize = 1 << 28;
+/// Device allocations are aligned like on real hardware
+const ALLOCATION_ALIGNMENT: u64 = 256;
+
+/// `Driver` executing kernels on the host by interpreting their PTX.
+///
+/// Only a subset of PTX is supported: data movement, integer arithmetic and the basic float
+/// instructions with default rounding. Modules using anything else fail to load, `unsupported`
+/// lets the runner skip such tests instead. Every launch runs synchronously, thread after thread.
+pub struct Interpreter {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    next_address: u64,
+    next_stream: usize,
+    /// Device allocations by their base address
+    memory: BTreeMap<u64, Vec<u8>>,
+    /// Length of every host allocation by its address
+    host_memory: HashMap<usize, usize>,
+    modules: Vec<Option<Vec<Kernel>>>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            state: Mutex::new(State {
+                next_address: 1 << 32,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+fn failure(code: u32, name: &str, description: impl Into<String>) -> DriverFailure {
+    DriverFailure {
+        code,
+        name: name.to_string(),
+        description: description.into(),
+    }
+}
+
+fn invalid_value(description: impl Into<String>) -> DriverFailure {
+    failure(1, "CUDA_ERROR_INVALID_VALUE", description)
+}
+
+fn invalid_handle() -> DriverFailure {
+    failure(400, "CUDA_ERROR_INVALID_HANDLE", "invalid resource handle")
+}
+
+fn illegal_address(address: u64) -> DriverFailure {
+    failure(
+        700,
+        "CUDA_ERROR_ILLEGAL_ADDRESS",
+        format!("access to unallocated device memory at {address:#x}"),
+    )
+}
+
+/// Handles are indices, offset by one so that they are never null.
+fn to_handle<T>(index: usize) -> *mut T {
+    ptr::without_provenance_mut(index + 1)
+}
+
+fn from_handle<T>(handle: *mut T) -> Option<usize> {
+    (handle.addr()).checked_sub(1)
+}
+
+impl State {
+    fn module(&self, module: CUmodule) -> DriverResult<&Vec<Kernel>> {
+        from_handle(module)
+            .and_then(|index| self.modules.get(index))
+            .and_then(Option::as_ref)
+            .ok_or_else(invalid_handle)
+    }
+
+    /// Device memory in `address..address + len`, which must lie in a single allocation.
+    fn device_slice(&mut self, address: u64, len: usize) -> DriverResult<&mut [u8]> {
+        let (base, allocation) = self
+            .memory
+            .range_mut(..=address)
+            .next_back()
+            .ok_or_else(|| illegal_address(address))?;
+        let offset = (address - base) as usize;
+        allocation
+            .get_mut(offset..offset + len)
+            .ok_or_else(|| illegal_address(address))
+    }
+}
+
+impl Driver for Interpreter {
+    fn device_count(&self) -> DriverResult<usize> {
+        Ok(1)
+    }
+
+    fn init(&self, device: usize) -> DriverResult<()> {
+        if device != 0 {
+            return Err(failure(
+                101,
+                "CUDA_ERROR_INVALID_DEVICE",
+                "invalid device ordinal",
+            ));
+        }
+        Ok(())
+    }
+
+    fn load_module(&self, image: &CStr) -> DriverResult<CUmodule> {
+        let text = image
+            .to_str()
+            .map_err(|_| failure(200, "CUDA_ERROR_INVALID_IMAGE", "PTX is not valid UTF-8"))?;
+        let kernels = parse_module(text)
+            .map_err(|err| failure(218, "CUDA_ERROR_INVALID_PTX", err.to_string()))?;
+        let mut state = self.state.lock().unwrap();
+        state.modules.push(Some(kernels));
+        Ok(to_handle(state.modules.len() - 1))
+    }
+
+    fn unload_module(&self, module: CUmodule) -> DriverResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let index = from_handle(module).ok_or_else(invalid_handle)?;
+        match state.modules.get_mut(index) {
+            Some(module @ Some(_)) => {
+                *module = None;
+                Ok(())
+            }
+            _ => Err(invalid_handle()),
+        }
+    }
+
+    fn get_function(&self, module: CUmodule, name: &CStr) -> DriverResult<CUfunction> {
+        let state = self.state.lock().unwrap();
+        let kernels = state.module(module)?;
+        let kernel = kernels
+            .iter()
+            .position(|kernel| kernel.name.as_bytes() == name.to_bytes())
+            .ok_or_else(|| failure(500, "CUDA_ERROR_NOT_FOUND", "named symbol not found"))?;
+        // The module handle is one-based, so the function handle never ends up null
+        Ok(ptr::without_provenance_mut((module.addr() << 16) | kernel))
+    }
+
+    fn mem_info(&self) -> DriverResult<(usize, usize)> {
+        let state = self.state.lock().unwrap();
+        let used: usize = state.memory.values().map(Vec::len).sum();
+        Ok((MEMORY_SIZE.saturating_sub(used), MEMORY_SIZE))
+    }
+
+    fn mem_alloc(&self, size: usize) -> DriverResult<CUdeviceptr> {
+        let mut state = self.state.lock().unwrap();
+        let address = state.next_address;
+        state.next_address =
+            (address + size as u64 + ALLOCATION_ALIGNMENT).next_multiple_of(ALLOCATION_ALIGNMENT);
+        state.memory.insert(address, vec![0; size]);
+        Ok(address)
+    }
+
+    fn mem_free(&self, ptr: CUdeviceptr) -> DriverResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .memory
+            .remove(&ptr)
+            .map(|_| ())
+            .ok_or_else(|| invalid_value(format!("{ptr:#x} is not a device allocation")))
+    }
+
+    fn mem_alloc_host(&self, size: usize) -> DriverResult<*mut u8> {
+        let len = size.max(1);
+        let ptr = Box::into_raw(vec![0u8; len].into_boxed_slice()).cast::<u8>();
+        let mut state = self.state.lock().unwrap();
+        state.host_memory.insert(ptr.addr(), len);
+        Ok(ptr)
+    }
+
+    unsafe fn mem_free_host(&self, ptr: *mut u8) -> DriverResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let len = state
+            .host_memory
+            .remove(&ptr.addr())
+            .ok_or_else(|| invalid_value("not a host allocation"))?;
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)));
+        Ok(())
+    }
+
+    fn stream_create(&self) -> DriverResult<CUstream> {
+        let mut state = self.state.lock().unwrap();
+        state.next_stream += 1;
+        Ok(to_handle(state.next_stream))
+    }
+
+    fn stream_destroy(&self, _: CUstream) -> DriverResult<()> {
+        Ok(())
+    }
+
+    fn stream_synchronize(&self, _: CUstream) -> DriverResult<()> {
+        Ok(())
+    }
+
+    unsafe fn memcpy_htod_async(
+        &self,
+        dst: CUdeviceptr,
+        src: *const u8,
+        size: usize,
+        _: CUstream,
+    ) -> DriverResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let dst = state.device_slice(dst, size)?;
+        ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), size);
+        Ok(())
+    }
+
+    unsafe fn memcpy_dtoh_async(
+        &self,
+        dst: *mut u8,
+        src: CUdeviceptr,
+        size: usize,
+        _: CUstream,
+    ) -> DriverResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let src = state.device_slice(src, size)?;
+        ptr::copy_nonoverlapping(src.as_ptr(), dst, size);
+        Ok(())
+    }
+
+    fn launch_kernel(
+        &self,
+        function: CUfunction,
+        grid_size: u32,
+        block_size: u32,
+        _: CUstream,
+        params: &[CUdeviceptr],
+    ) -> DriverResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let kernel = state
+            .module(ptr::without_provenance_mut(function.addr() >> 16))?
+            .get(function.addr() & 0xffff)
+            .ok_or_else(invalid_handle)?
+            .clone();
+        if params.len() != kernel.params.len() {
+            return Err(invalid_value(format!(
+                "kernel {} takes {} parameters, got {}",
+                kernel.name,
+                kernel.params.len(),
+                params.len()
+            )));
+        }
+        let mut registers = vec![0u64; kernel.registers.len()];
+        for block in 0..grid_size {
+            for thread in 0..block_size {
+                let ids = ThreadIds {
+                    tid: thread,
+                    ntid: block_size,
+                    ctaid: block,
+                    nctaid: grid_size,
+                };
+                registers.fill(0);
+                kernel.execute(&mut state, params, &ids, &mut registers)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Pred,
+    B(u32),
+    U(u32),
+    S(u32),
+    F(u32),
+}
+
+impl Type {
+    fn parse(text: &str) -> Option<Self> {
+        if text == "pred" {
+            return Some(Type::Pred);
+        }
+        let (kind, bits) = text.split_at_checked(1)?;
+        let bits = bits.parse().ok()?;
+        let ty = match (kind, bits) {
+            ("b" | "u" | "s", 8 | 16 | 32 | 64) | ("f", 32 | 64) => match kind {
+                "b" => Type::B(bits),
+                "u" => Type::U(bits),
+                "s" => Type::S(bits),
+                _ => Type::F(bits),
+            },
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Type::Pred => 1,
+            Type::B(bits) | Type::U(bits) | Type::S(bits) | Type::F(bits) => bits,
+        }
+    }
+
+    fn is_signed(self) -> bool {
+        matches!(self, Type::S(_))
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Type::F(_))
+    }
+
+    fn is_int(self) -> bool {
+        matches!(self, Type::B(_) | Type::U(_) | Type::S(_))
+    }
+
+    fn mask(self) -> u64 {
+        mask(self.bits())
+    }
+
+    /// Integer type of the same signedness and twice the width, used by `.wide` instructions.
+    fn wide(self) -> Option<Self> {
+        match self {
+            Type::U(bits @ (16 | 32)) => Some(Type::U(bits * 2)),
+            Type::S(bits @ (16 | 32)) => Some(Type::S(bits * 2)),
+            _ => None,
+        }
+    }
+
+    /// Truncate `value` to this type, extending it back to 64 bits according to its signedness.
+    fn extend(self, value: u64) -> u64 {
+        let bits = self.bits();
+        if bits == 64 {
+            value
+        } else if self.is_signed() {
+            (((value << (64 - bits)) as i64) >> (64 - bits)) as u64
+        } else {
+            value & self.mask()
+        }
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits == 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Special {
+    Tid,
+    Ntid,
+    Ctaid,
+    Nctaid,
+    /// `.y` and `.z` components of a one-dimensional launch
+    Zero,
+    One,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Register(usize),
+    Immediate(u64),
+    /// Decimal float literal, converted when the instruction type is known
+    Float(f64),
+    Special(Special),
+    Address {
+        register: usize,
+        offset: i64,
+    },
+    Param(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Lo,
+    Ls,
+    Hi,
+    Hs,
+    Equ,
+    Neu,
+    Ltu,
+    Leu,
+    Gtu,
+    Geu,
+    Num,
+    Nan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MulMode {
+    Lo,
+    Hi,
+    Wide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FloatToInt {
+    Rzi,
+    Rni,
+    Rmi,
+    Rpi,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Mov(Type),
+    LdParam,
+    Ld(Type),
+    St(Type),
+    Cvt {
+        dst: Type,
+        src: Type,
+        rounding: Option<FloatToInt>,
+    },
+    Add(Type),
+    Sub(Type),
+    Mul(MulMode, Type),
+    Mad(MulMode, Type),
+    Fma(Type),
+    Min(Type),
+    Max(Type),
+    Neg(Type),
+    Abs(Type),
+    And(Type),
+    Or(Type),
+    Xor(Type),
+    Not(Type),
+    Shl(Type),
+    Shr(Type),
+    Brev(Type),
+    Setp(Compare, Type),
+    Selp(Type),
+    Ret,
+}
+
+#[derive(Debug, Clone)]
+struct Instruction {
+    op: Op,
+    ftz: bool,
+    sat: bool,
+    operands: Vec<Operand>,
+}
+
+#[derive(Debug, Clone)]
+struct Kernel {
+    name: String,
+    params: Vec<String>,
+    registers: Vec<Type>,
+    instructions: Vec<Instruction>,
+}
+
+struct ThreadIds {
+    tid: u32,
+    ntid: u32,
+    ctaid: u32,
+    nctaid: u32,
+}
+
+/// Remove `//` and `/* */` comments, a block comment becomes a space so it still separates tokens.
+fn strip_comments(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('/') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("//") {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+        } else if rest.starts_with("/*") {
+            result.push(' ');
+            rest = rest[2..].find("*/").map_or("", |end| &rest[2 + end + 2..]);
+        } else {
+            result.push('/');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Why a module can't be loaded by the interpreter.
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// Valid PTX the interpreter can't execute, e.g. an instruction it doesn't implement
+    Unsupported(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Unsupported(message) | ParseError::Invalid(message) => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+/// Why the interpreter can't execute `ptx`, `None` if it can or if the PTX is invalid. Tests
+/// using such PTX are skipped, invalid PTX still fails to compile.
+pub fn unsupported(ptx: &str) -> Option<String> {
+    match parse_module(ptx) {
+        Err(ParseError::Unsupported(message)) => Some(message),
+        Ok(_) | Err(ParseError::Invalid(_)) => None,
+    }
+}
+
+/// Parse every `.entry` of a module, fails on anything the interpreter can't execute.
+fn parse_module(text: &str) -> Result<Vec<Kernel>, ParseError> {
+    use ParseError::*;
+
+    let text = strip_comments(text);
+    let mut kernels = Vec::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find(".entry") {
+        rest = &rest[start + ".entry".len()..];
+        let missing = |what: &str| Invalid(format!("missing kernel {what}"));
+        let params_start = rest.find('(').ok_or_else(|| missing("parameters"))?;
+        let name = rest[..params_start].trim().to_string();
+        let params_end = rest.find(')').ok_or_else(|| missing("parameters"))?;
+        let params = rest[params_start + 1..params_end]
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(parse_param)
+            .collect::<Result<_, _>>()?;
+        let body_start = rest.find('{').ok_or_else(|| missing("body"))?;
+        let body_end = rest.find('}').ok_or_else(|| missing("body"))?;
+        let body = &rest[body_start + 1..body_end];
+        if body.contains('{') {
+            return Err(Unsupported("nested blocks are not supported".to_string()));
+        }
+        kernels.push(parse_kernel(name, params, body)?);
+        rest = &rest[body_end + 1..];
+    }
+    if kernels.is_empty() {
+        return Err(Invalid("no kernels found".to_string()));
+    }
+    Ok(kernels)
+}
+
+fn parse_param(param: &str) -> Result<String, ParseError> {
+    match param.split_whitespace().collect::<Vec<_>>()[..] {
+        [".param", ".u64" | ".b64" | ".s64", name] => Ok(name.to_string()),
+        _ => Err(ParseError::Unsupported(format!(
+            "unsupported kernel parameter `{param}`"
+        ))),
+    }
+}
+
+fn parse_kernel(name: String, params: Vec<String>, body: &str) -> Result<Kernel, ParseError> {
+    use ParseError::*;
+
+    let statements: Vec<_> = body
+        .split(';')
+        .map(|statement| statement.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|statement| !statement.is_empty())
+        .collect();
+    // Registers may be declared after their first use, so collect them upfront
+    let mut register_names = HashMap::new();
+    let mut registers = Vec::new();
+    for statement in statements.iter() {
+        let Some(declaration) = statement.strip_prefix(".reg ") else {
+            continue;
+        };
+        let (ty, names) = declaration
+            .split_once(' ')
+            .ok_or_else(|| Invalid(format!("invalid register declaration `{statement}`")))?;
+        let ty = ty
+            .strip_prefix('.')
+            .and_then(Type::parse)
+            .ok_or_else(|| Unsupported(format!("unsupported register type in `{statement}`")))?;
+        for name in names.split(',').map(str::trim) {
+            if name.contains('<') {
+                return Err(Unsupported(format!(
+                    "register ranges are not supported: `{statement}`"
+                )));
+            }
+            register_names.insert(name.to_string(), registers.len());
+            registers.push(ty);
+        }
+    }
+    let parser = Parser {
+        params: &params,
+        registers: &register_names,
+    };
+    let instructions = statements
+        .iter()
+        .filter(|statement| !statement.starts_with(".reg "))
+        .map(|statement| parser.instruction(statement))
+        .collect::<Result<_, _>>()?;
+    Ok(Kernel {
+        name,
+        params,
+        registers,
+        instructions,
+    })
+}
+
+struct Parser<'a> {
+    params: &'a [String],
+    registers: &'a HashMap<String, usize>,
+}
+
+impl Parser<'_> {
+    fn instruction(&self, statement: &str) -> Result<Instruction, ParseError> {
+        use ParseError::*;
+
+        if statement.starts_with('@') || statement.contains(':') {
+            return Err(Unsupported(format!(
+                "predicates and labels are not supported: `{statement}`"
+            )));
+        }
+        let (opcode, operands) = statement.split_once(' ').unwrap_or((statement, ""));
+        // Reported without operands, so that all tests of an instruction give the same reason
+        let unsupported = || Unsupported(format!("unsupported instruction `{opcode}`"));
+        let mut parts = opcode.split('.');
+        let name = parts.next().unwrap_or_default();
+        let mut types = Vec::new();
+        let mut modifiers = Vec::new();
+        for part in parts {
+            match Type::parse(part) {
+                Some(ty) => types.push(ty),
+                None => modifiers.push(part),
+            }
+        }
+        let mut ftz = false;
+        let mut sat = false;
+        let mut rn = false;
+        let mut mode = None;
+        let mut rounding = None;
+        let mut compare = None;
+        let mut state_space = None;
+        for modifier in modifiers {
+            // Compares go first, `lo` and `hi` of setp are unsigned comparisons, not `MulMode`s
+            if name == "setp" {
+                if let Some(cmp) = parse_compare(modifier) {
+                    compare = Some(cmp);
+                    continue;
+                }
+            }
+            match modifier {
+                "ftz" => ftz = true,
+                "sat" => sat = true,
+                "rn" => rn = true,
+                "lo" => mode = Some(MulMode::Lo),
+                "hi" => mode = Some(MulMode::Hi),
+                "wide" => mode = Some(MulMode::Wide),
+                "rzi" => rounding = Some(FloatToInt::Rzi),
+                "rni" => rounding = Some(FloatToInt::Rni),
+                "rmi" => rounding = Some(FloatToInt::Rmi),
+                "rpi" => rounding = Some(FloatToInt::Rpi),
+                "global" | "param" => state_space = Some(modifier),
+                _ => return Err(unsupported()),
+            }
+        }
+
+        let op = match (name, &types[..]) {
+            ("ret", []) => Op::Ret,
+            ("mov", [ty]) => Op::Mov(*ty),
+            ("ld", [ty]) if state_space == Some("param") && ty.bits() == 64 => Op::LdParam,
+            ("ld", [ty]) if state_space != Some("param") && *ty != Type::Pred => Op::Ld(*ty),
+            ("st", [ty]) if state_space != Some("param") && *ty != Type::Pred => Op::St(*ty),
+            ("cvt", [dst, src]) if *dst != Type::Pred && *src != Type::Pred => {
+                let valid = match (dst.is_float(), src.is_float()) {
+                    (false, false) => rounding.is_none() && !rn && !ftz,
+                    (true, false) => rounding.is_none() && rn,
+                    (false, true) => rounding.is_some() && !rn,
+                    // Widening is exact, narrowing has to round
+                    (true, true) => rounding.is_none() && (rn == (dst.bits() < src.bits())),
+                };
+                if !valid {
+                    return Err(unsupported());
+                }
+                Op::Cvt {
+                    dst: *dst,
+                    src: *src,
+                    rounding,
+                }
+            }
+            ("add", [ty]) => Op::Add(*ty),
+            ("sub", [ty]) => Op::Sub(*ty),
+            ("mul", [ty]) if ty.is_float() => Op::Mul(MulMode::Lo, *ty),
+            ("mul", [ty]) if ty.is_int() && mode.is_some() => Op::Mul(mode.unwrap(), *ty),
+            ("mad", [ty]) if ty.is_int() && mode.is_some() => Op::Mad(mode.unwrap(), *ty),
+            ("mad" | "fma", [ty]) if ty.is_float() && rn => Op::Fma(*ty),
+            ("min", [ty]) => Op::Min(*ty),
+            ("max", [ty]) => Op::Max(*ty),
+            ("neg", [ty]) => Op::Neg(*ty),
+            ("abs", [ty]) => Op::Abs(*ty),
+            ("and", [ty]) => Op::And(*ty),
+            ("or", [ty]) => Op::Or(*ty),
+            ("xor", [ty]) => Op::Xor(*ty),
+            ("not", [ty]) => Op::Not(*ty),
+            ("shl", [ty]) if matches!(ty, Type::B(16 | 32 | 64)) => Op::Shl(*ty),
+            ("shr", [ty]) if ty.is_int() && ty.bits() >= 16 => Op::Shr(*ty),
+            ("brev", [ty]) if matches!(ty, Type::B(32 | 64)) => Op::Brev(*ty),
+            ("setp", [ty]) if compare.is_some() => Op::Setp(compare.unwrap(), *ty),
+            ("selp", [ty]) => Op::Selp(*ty),
+            _ => return Err(unsupported()),
+        };
+
+        // Only check that the modifiers make sense for the instruction, not every PTX rule
+        let float_op = match op {
+            Op::Add(ty) | Op::Sub(ty) | Op::Mul(_, ty) | Op::Fma(ty) => ty.is_float(),
+            Op::Min(ty) | Op::Max(ty) | Op::Neg(ty) | Op::Abs(ty) => ty.is_float(),
+            _ => false,
+        };
+        let allows_rn = float_op || matches!(op, Op::Cvt { .. });
+        let allows_ftz = float_op || matches!(op, Op::Cvt { .. } | Op::Setp(..));
+        let allows_sat = float_op && !matches!(op, Op::Min(_) | Op::Max(_) | Op::Neg(_))
+            || matches!(
+                op,
+                Op::Add(Type::S(32)) | Op::Sub(Type::S(32)) | Op::Cvt { .. }
+            );
+        let allows_mode = matches!(op, Op::Mul(..) | Op::Mad(..)) && !float_op;
+        let allows_rounding = matches!(op, Op::Cvt { .. });
+        if (rn && !allows_rn)
+            || (ftz && !allows_ftz)
+            || (sat && !allows_sat)
+            || (mode.is_some() && !allows_mode)
+            || (rounding.is_some() && !allows_rounding)
+            || (state_space.is_some() && !matches!(op, Op::Ld(_) | Op::St(_) | Op::LdParam))
+            || (state_space == Some("param") && !matches!(op, Op::LdParam))
+        {
+            return Err(unsupported());
+        }
+        if let Op::Mul(MulMode::Wide, ty) | Op::Mad(MulMode::Wide, ty) = op {
+            ty.wide().ok_or_else(unsupported)?;
+        }
+
+        let operands: Vec<_> = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .map(|operand| self.operand(operand))
+            .collect::<Result<_, _>>()?;
+        let expected = match op {
+            Op::Ret => 0,
+            Op::Mov(_) | Op::LdParam | Op::Ld(_) | Op::St(_) | Op::Cvt { .. } => 2,
+            Op::Neg(_) | Op::Abs(_) | Op::Not(_) | Op::Brev(_) => 2,
+            Op::Mad(..) | Op::Fma(_) | Op::Selp(_) => 4,
+            _ => 3,
+        };
+        let valid_operands = operands.len() == expected
+            && match op {
+                Op::LdParam => matches!(operands[..], [Operand::Register(_), Operand::Param(_)]),
+                Op::Ld(_) => matches!(
+                    operands[..],
+                    [Operand::Register(_), Operand::Address { .. }]
+                ),
+                Op::St(_) => {
+                    matches!(operands[0], Operand::Address { .. })
+                        && !matches!(operands[1], Operand::Address { .. } | Operand::Param(_))
+                }
+                _ => {
+                    matches!(operands.first(), None | Some(Operand::Register(_)))
+                        && operands
+                            .iter()
+                            .skip(1)
+                            .all(|o| !matches!(o, Operand::Address { .. } | Operand::Param(_)))
+                }
+            };
+        if !valid_operands {
+            return Err(Invalid(format!("invalid operands in `{statement}`")));
+        }
+        Ok(Instruction {
+            op,
+            ftz,
+            sat,
+            operands,
+        })
+    }
+
+    fn operand(&self, text: &str) -> Result<Operand, ParseError> {
+        use ParseError::*;
+
+        let invalid = |what: &str| Invalid(format!("{what} `{text}`"));
+        if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            let inner = inner.trim();
+            if let Some(param) = self.params.iter().position(|p| p == inner) {
+                return Ok(Operand::Param(param));
+            }
+            let (register, offset) = match inner.split_once('+') {
+                Some((register, offset)) => (
+                    register.trim(),
+                    parse_int(offset.trim()).ok_or_else(|| invalid("invalid address"))? as i64,
+                ),
+                None => (inner, 0),
+            };
+            let register = *self
+                .registers
+                .get(register)
+                .ok_or_else(|| invalid("unknown register in address"))?;
+            return Ok(Operand::Address { register, offset });
+        }
+        if let Some(special) = text.strip_prefix('%') {
+            let special = match special {
+                "tid.x" => Special::Tid,
+                "ntid.x" => Special::Ntid,
+                "ctaid.x" => Special::Ctaid,
+                "nctaid.x" => Special::Nctaid,
+                "tid.y" | "tid.z" | "ctaid.y" | "ctaid.z" => Special::Zero,
+                "ntid.y" | "ntid.z" | "nctaid.y" | "nctaid.z" => Special::One,
+                _ => {
+                    return Err(Unsupported(format!(
+                        "unsupported special register `{text}`"
+                    )))
+                }
+            };
+            return Ok(Operand::Special(special));
+        }
+        if let Some(register) = self.registers.get(text) {
+            return Ok(Operand::Register(*register));
+        }
+        if let Some(bits) = text
+            .strip_prefix("0f")
+            .or_else(|| text.strip_prefix("0F"))
+            .or_else(|| text.strip_prefix("0d"))
+            .or_else(|| text.strip_prefix("0D"))
+        {
+            return u64::from_str_radix(bits, 16)
+                .map(Operand::Immediate)
+                .map_err(|_| invalid("invalid float literal"));
+        }
+        if let Some(value) = parse_int(text) {
+            return Ok(Operand::Immediate(value));
+        }
+        if let Ok(value) = text.parse::<f64>() {
+            return Ok(Operand::Float(value));
+        }
+        Err(invalid("unknown operand"))
+    }
+}
+
+fn parse_int(text: &str) -> Option<u64> {
+    let text = text.strip_suffix('U').unwrap_or(text);
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+fn parse_compare(text: &str) -> Option<Compare> {
+    Some(match text {
+        "eq" => Compare::Eq,
+        "ne" => Compare::Ne,
+        "lt" => Compare::Lt,
+        "le" => Compare::Le,
+        "gt" => Compare::Gt,
+        "ge" => Compare::Ge,
+        "lo" => Compare::Lo,
+        "ls" => Compare::Ls,
+        "hi" => Compare::Hi,
+        "hs" => Compare::Hs,
+        "equ" => Compare::Equ,
+        "neu" => Compare::Neu,
+        "ltu" => Compare::Ltu,
+        "leu" => Compare::Leu,
+        "gtu" => Compare::Gtu,
+        "geu" => Compare::Geu,
+        "num" => Compare::Num,
+        "nan" => Compare::Nan,
+        _ => return None,
+    })
+}
+
+/// Float values are handled as `f64`, which represents every `f32` exactly.
+#[derive(Debug, Clone, Copy)]
+struct Float {
+    bits: u32,
+}
+
+impl Float {
+    fn decode(self, value: u64) -> f64 {
+        if self.bits == 32 {
+            f32::from_bits(value as u32) as f64
+        } else {
+            f64::from_bits(value)
+        }
+    }
+
+    /// Round to the type, NaNs become the canonical NaN returned by NVIDIA GPUs.
+    fn encode(self, value: f64) -> u64 {
+        if self.bits == 32 {
+            if value.is_nan() {
+                0x7fffffff
+            } else {
+                (value as f32).to_bits() as u64
+            }
+        } else if value.is_nan() {
+            0x7fffffffffffffff
+        } else {
+            value.to_bits()
+        }
+    }
+
+    fn flush(self, value: f64, ftz: bool) -> f64 {
+        let subnormal = if self.bits == 32 {
+            (value as f32).is_subnormal()
+        } else {
+            value.is_subnormal()
+        };
+        if ftz && subnormal {
+            0.0f64.copysign(value)
+        } else {
+            value
+        }
+    }
+}
+
+/// Clamp to [0.0, 1.0], NaN and -0.0 become +0.0.
+fn saturate(value: f64) -> f64 {
+    if value.is_nan() || value <= 0.0 {
+        0.0
+    } else {
+        value.min(1.0)
+    }
+}
+
+impl Kernel {
+    fn execute(
+        &self,
+        state: &mut State,
+        params: &[CUdeviceptr],
+        ids: &ThreadIds,
+        registers: &mut [u64],
+    ) -> DriverResult<()> {
+        for instruction in self.instructions.iter() {
+            let operands = &instruction.operands;
+            let read = |index: usize, ty: Type| -> u64 {
+                let value = match operands[index] {
+                    Operand::Register(register) => registers[register],
+                    Operand::Immediate(value) => value,
+                    Operand::Float(value) => Float { bits: ty.bits() }.encode(value),
+                    Operand::Special(special) => match special {
+                        Special::Tid => ids.tid as u64,
+                        Special::Ntid => ids.ntid as u64,
+                        Special::Ctaid => ids.ctaid as u64,
+                        Special::Nctaid => ids.nctaid as u64,
+                        Special::Zero => 0,
+                        Special::One => 1,
+                    },
+                    Operand::Address { .. } | Operand::Param(_) => unreachable!(),
+                };
+                ty.extend(value)
+            };
+            let address = |index: usize| match operands[index] {
+                Operand::Address { register, offset } => {
+                    registers[register].wrapping_add_signed(offset)
+                }
+                _ => unreachable!(),
+            };
+            let destination = match operands.first() {
+                Some(Operand::Register(register)) => *register,
+                _ => usize::MAX,
+            };
+            let ftz = instruction.ftz;
+            let sat = instruction.sat;
+
+            let result = match instruction.op {
+                Op::Ret => return Ok(()),
+                Op::Mov(ty) => read(1, ty),
+                Op::LdParam => match operands[1] {
+                    Operand::Param(param) => params[param],
+                    _ => unreachable!(),
+                },
+                Op::Ld(ty) => {
+                    let size = ty.bits() as usize / 8;
+                    let mut bytes = [0u8; 8];
+                    bytes[..size].copy_from_slice(state.device_slice(address(1), size)?);
+                    ty.extend(u64::from_le_bytes(bytes))
+                }
+                Op::St(ty) => {
+                    let size = ty.bits() as usize / 8;
+                    let value = read(1, ty).to_le_bytes();
+                    state
+                        .device_slice(address(0), size)?
+                        .copy_from_slice(&value[..size]);
+                    continue;
+                }
+                Op::Cvt { dst, src, rounding } => {
+                    let value = read(1, src);
+                    convert(dst, src, value, rounding, ftz, sat)
+                }
+                Op::Add(ty) | Op::Sub(ty) if ty.is_float() => {
+                    let float = Float { bits: ty.bits() };
+                    let a = float.flush(float.decode(read(1, ty)), ftz);
+                    let b = float.flush(float.decode(read(2, ty)), ftz);
+                    let result = match instruction.op {
+                        Op::Add(_) if float.bits == 32 => (a as f32 + b as f32) as f64,
+                        Op::Add(_) => a + b,
+                        _ if float.bits == 32 => (a as f32 - b as f32) as f64,
+                        _ => a - b,
+                    };
+                    float_result(float, result, ftz, sat)
+                }
+                Op::Add(ty) | Op::Sub(ty) => {
+                    let (a, b) = (read(1, ty), read(2, ty));
+                    let add = matches!(instruction.op, Op::Add(_));
+                    if sat {
+                        let (a, b) = (a as i32, b as i32);
+                        let result = if add {
+                            a.saturating_add(b)
+                        } else {
+                            a.saturating_sub(b)
+                        };
+                        result as u64
+                    } else if add {
+                        a.wrapping_add(b)
+                    } else {
+                        a.wrapping_sub(b)
+                    }
+                }
+                Op::Mul(_, ty) if ty.is_float() => {
+                    let float = Float { bits: ty.bits() };
+                    let a = float.flush(float.decode(read(1, ty)), ftz);
+                    let b = float.flush(float.decode(read(2, ty)), ftz);
+                    let result = if float.bits == 32 {
+                        (a as f32 * b as f32) as f64
+                    } else {
+                        a * b
+                    };
+                    float_result(float, result, ftz, sat)
+                }
+                Op::Mul(mode, ty) => multiply(mode, ty, read(1, ty), read(2, ty)),
+                Op::Mad(mode, ty) => {
+                    let product = multiply(mode, ty, read(1, ty), read(2, ty));
+                    let addend_type = if mode == MulMode::Wide {
+                        ty.wide().unwrap()
+                    } else {
+                        ty
+                    };
+                    product.wrapping_add(read(3, addend_type))
+                }
+                Op::Fma(ty) => {
+                    let float = Float { bits: ty.bits() };
+                    let [a, b, c] = [1, 2, 3].map(|i| float.flush(float.decode(read(i, ty)), ftz));
+                    let result = if float.bits == 32 {
+                        (a as f32).mul_add(b as f32, c as f32) as f64
+                    } else {
+                        a.mul_add(b, c)
+                    };
+                    float_result(float, result, ftz, sat)
+                }
+                Op::Min(ty) | Op::Max(ty) if ty.is_float() => {
+                    let float = Float { bits: ty.bits() };
+                    let a = float.flush(float.decode(read(1, ty)), ftz);
+                    let b = float.flush(float.decode(read(2, ty)), ftz);
+                    let min = matches!(instruction.op, Op::Min(_));
+                    let result = if a.is_nan() {
+                        b
+                    } else if b.is_nan() {
+                        a
+                    } else if a == b {
+                        // -0.0 is smaller than +0.0
+                        if min == (a.is_sign_negative()) {
+                            a
+                        } else {
+                            b
+                        }
+                    } else if min == (a < b) {
+                        a
+                    } else {
+                        b
+                    };
+                    float.encode(result)
+                }
+                Op::Min(ty) | Op::Max(ty) => {
+                    let (a, b) = (read(1, ty), read(2, ty));
+                    let a_smaller = if ty.is_signed() {
+                        (a as i64) < (b as i64)
+                    } else {
+                        a < b
+                    };
+                    if matches!(instruction.op, Op::Min(_)) == a_smaller {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                Op::Neg(ty) | Op::Abs(ty) if ty.is_float() => {
+                    let float = Float { bits: ty.bits() };
+                    let sign = 1u64 << (ty.bits() - 1);
+                    let mut value = read(1, ty);
+                    if ftz && float.flush(float.decode(value), true) == 0.0 {
+                        value &= sign;
+                    }
+                    if matches!(instruction.op, Op::Neg(_)) {
+                        value ^ sign
+                    } else {
+                        value & !sign
+                    }
+                }
+                Op::Neg(ty) => read(1, ty).wrapping_neg(),
+                Op::Abs(ty) => (read(1, ty) as i64).wrapping_abs() as u64,
+                Op::And(ty) => read(1, ty) & read(2, ty),
+                Op::Or(ty) => read(1, ty) | read(2, ty),
+                Op::Xor(ty) => read(1, ty) ^ read(2, ty),
+                Op::Not(ty) => !read(1, ty),
+                Op::Shl(ty) => {
+                    let amount = read(2, Type::U(32));
+                    if amount >= ty.bits() as u64 {
+                        0
+                    } else {
+                        read(1, ty) << amount
+                    }
+                }
+                Op::Shr(ty) => {
+                    let amount = read(2, Type::U(32)).min(63);
+                    if ty.is_signed() {
+                        ((read(1, ty) as i64) >> amount) as u64
+                    } else if amount >= ty.bits() as u64 {
+                        0
+                    } else {
+                        read(1, ty) >> amount
+                    }
+                }
+                Op::Brev(ty) => read(1, ty).reverse_bits() >> (64 - ty.bits()),
+                Op::Setp(compare, ty) => {
+                    compare_values(compare, ty, read(1, ty), read(2, ty), ftz) as u64
+                }
+                Op::Selp(ty) => {
+                    if read(3, Type::Pred) != 0 {
+                        read(1, ty)
+                    } else {
+                        read(2, ty)
+                    }
+                }
+            };
+            let ty = self.registers[destination];
+            registers[destination] = result & ty.mask();
+        }
+        Ok(())
+    }
+}
+
+fn float_result(float: Float, value: f64, ftz: bool, sat: bool) -> u64 {
+    let value = if sat { saturate(value) } else { value };
+    float.encode(float.flush(value, ftz))
+}
+
+/// Integer multiplication, `value`s are extended from `ty` according to its signedness.
+fn multiply(mode: MulMode, ty: Type, a: u64, b: u64) -> u64 {
+    let bits = ty.bits();
+    let product = if ty.is_signed() {
+        (a as i64 as i128).wrapping_mul(b as i64 as i128) as u128
+    } else {
+        (a as u128).wrapping_mul(b as u128)
+    };
+    match mode {
+        MulMode::Lo => product as u64,
+        MulMode::Wide => product as u64,
+        MulMode::Hi => (product >> bits) as u64,
+    }
+}
+
+fn compare_values(compare: Compare, ty: Type, a: u64, b: u64, ftz: bool) -> bool {
+    if ty.is_float() {
+        let float = Float { bits: ty.bits() };
+        let a = float.flush(float.decode(a), ftz);
+        let b = float.flush(float.decode(b), ftz);
+        let unordered = a.is_nan() || b.is_nan();
+        return match compare {
+            Compare::Eq => a == b,
+            Compare::Ne => !unordered && a != b,
+            Compare::Lt => a < b,
+            Compare::Le => a <= b,
+            Compare::Gt => a > b,
+            Compare::Ge => a >= b,
+            Compare::Equ => unordered || a == b,
+            Compare::Neu => a != b,
+            Compare::Ltu => unordered || a < b,
+            Compare::Leu => unordered || a <= b,
+            Compare::Gtu => unordered || a > b,
+            Compare::Geu => unordered || a >= b,
+            Compare::Num => !unordered,
+            Compare::Nan => unordered,
+            Compare::Lo | Compare::Ls | Compare::Hi | Compare::Hs => false,
+        };
+    }
+    let (signed_a, signed_b) = (a as i64, b as i64);
+    let signed = ty.is_signed();
+    match compare {
+        Compare::Eq => a == b,
+        Compare::Ne => a != b,
+        Compare::Lt if signed => signed_a < signed_b,
+        Compare::Le if signed => signed_a <= signed_b,
+        Compare::Gt if signed => signed_a > signed_b,
+        Compare::Ge if signed => signed_a >= signed_b,
+        Compare::Lt | Compare::Lo => a < b,
+        Compare::Le | Compare::Ls => a <= b,
+        Compare::Gt | Compare::Hi => a > b,
+        Compare::Ge | Compare::Hs => a >= b,
+        _ => false,
+    }
+}
+
+fn convert(
+    dst: Type,
+    src: Type,
+    value: u64,
+    rounding: Option<FloatToInt>,
+    ftz: bool,
+    sat: bool,
+) -> u64 {
+    match (dst.is_float(), src.is_float()) {
+        (false, false) => {
+            if !sat {
+                return value;
+            }
+            let (min, max) = if dst.is_signed() {
+                let max = mask(dst.bits() - 1) as i128;
+                (-max - 1, max)
+            } else {
+                (0, mask(dst.bits()) as i128)
+            };
+            let value = if src.is_signed() {
+                value as i64 as i128
+            } else {
+                value as i128
+            };
+            value.clamp(min, max) as u64
+        }
+        (true, false) => {
+            let value = match (dst.bits(), src.is_signed()) {
+                (32, true) => value as i64 as f32 as f64,
+                (32, false) => value as f32 as f64,
+                (_, true) => value as i64 as f64,
+                (_, false) => value as f64,
+            };
+            float_result(Float { bits: dst.bits() }, value, ftz, sat)
+        }
+        (false, true) => {
+            let float = Float { bits: src.bits() };
+            let value = float.flush(float.decode(value), ftz);
+            let value = match rounding.unwrap() {
+                FloatToInt::Rzi => value.trunc(),
+                FloatToInt::Rni => value.round_ties_even(),
+                FloatToInt::Rmi => value.floor(),
+                FloatToInt::Rpi => value.ceil(),
+            };
+            // Conversions to integers always saturate and NaN becomes 0, just like `as`
+            match dst {
+                Type::S(8) => value as i8 as u64,
+                Type::S(16) => value as i16 as u64,
+                Type::S(32) => value as i32 as u64,
+                Type::S(_) => value as i64 as u64,
+                Type::U(8) | Type::B(8) => value as u8 as u64,
+                Type::U(16) | Type::B(16) => value as u16 as u64,
+                Type::U(32) | Type::B(32) => value as u32 as u64,
+                _ => value as u64,
+            }
+        }
+        (true, true) => {
+            let float = Float { bits: src.bits() };
+            let value = float.flush(float.decode(value), ftz);
+            float_result(Float { bits: dst.bits() }, value, ftz, sat)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::*;
+
+    /// Module with a kernel that loads `x` from its input, runs `body` and stores `y` to its
+    /// output, one 32-bit element for every thread.
+    fn module(body: &str) -> String {
+        format!(
+            "
+    .version 7.8
+    .target sm_90
+    .address_size 64
+
+.entry run(.param .u64 input, .param .u64 output)
+{{
+.reg .u64 input_addr;
+.reg .u64 output_addr;
+.reg .u32 id;
+.reg .u32 ntid;
+.reg .u32 ctaid;
+.reg .u64 offset;
+.reg .b32 x, y;
+ld.param.u64 input_addr, [input];
+ld.param.u64 output_addr, [output];
+mov.u32 id, %tid.x;
+mov.u32 ntid, %ntid.x;
+mov.u32 ctaid, %ctaid.x;
+mad.lo.u32 id, ctaid, ntid, id;
+mul.wide.u32 offset, id, 4;
+add.u64 input_addr, input_addr, offset;
+add.u64 output_addr, output_addr, offset;
+ld.global.u32 x, [input_addr];
+{body}
+st.global.u32 [output_addr], y;
+ret;
+}}"
+        )
+    }
+
+    /// Run `body` on every element of `input` in `grid` blocks, returns the outputs.
+    fn launch(body: &str, input: &[u32], grid: u32) -> Vec<u32> {
+        let driver = Interpreter::new();
+        driver.init(0).unwrap();
+        let image = CString::new(module(body)).unwrap();
+        let module = driver.load_module(&image).unwrap();
+        let function = driver.get_function(module, c"run").unwrap();
+        let size = std::mem::size_of_val(input);
+        let dev_input = driver.mem_alloc(size).unwrap();
+        let dev_output = driver.mem_alloc(size).unwrap();
+        let stream = driver.stream_create().unwrap();
+        let mut output = vec![0u32; input.len()];
+        unsafe {
+            driver
+                .memcpy_htod_async(dev_input, input.as_ptr().cast(), size, stream)
+                .unwrap();
+            driver
+                .launch_kernel(
+                    function,
+                    grid,
+                    input.len() as u32 / grid,
+                    stream,
+                    &[dev_input, dev_output],
+                )
+                .unwrap();
+            driver
+                .memcpy_dtoh_async(output.as_mut_ptr().cast(), dev_output, size, stream)
+                .unwrap();
+        }
+        output
+    }
+
+    #[test]
+    fn thread_ids() {
+        let output = launch("mov.u32 y, id;", &[0; 8], 2);
+        assert_eq!(output, [0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn integer_arithmetic_wraps() {
+        let output = launch("mad.lo.u32 y, x, 3, 7;", &[0, 1, 0xFFFFFFFF, 0x80000000], 1);
+        assert_eq!(output, [7, 10, 4, 0x80000007]);
+    }
+
+    #[test]
+    fn shifts_and_bit_reversal() {
+        let output = launch("shr.s32 y, x, 4;", &[0x80000000, 0x7FFFFFF0], 1);
+        assert_eq!(output, [0xF8000000, 0x07FFFFFF]);
+        let output = launch("brev.b32 y, x;", &[1, 0xF0000000], 1);
+        assert_eq!(output, [0x80000000, 0x0000000F]);
+    }
+
+    #[test]
+    fn float_add_rounds_to_nearest_even() {
+        // 1.0, 2^24 where adding 1.0 is a tie, infinity and the smallest subnormal
+        let input = [0x3F800000, 0x4B800000, 0x7F800000, 0x00000001];
+        let output = launch("add.rn.f32 y, x, 0f3F800000;", &input, 1);
+        assert_eq!(output, [0x40000000, 0x4B800000, 0x7F800000, 0x3F800000]);
+        let output = launch("add.rn.f32 y, x, 0f00000000;", &input[3..], 1);
+        assert_eq!(output, [0x00000001]);
+        let output = launch("add.rn.ftz.f32 y, x, 0f00000000;", &input[3..], 1);
+        assert_eq!(output, [0x00000000]);
+    }
+
+    #[test]
+    fn float_to_int_saturates() {
+        // -2.5, 2.5, 1e10 and NaN
+        let input = [0xC0200000, 0x40200000, 0x501502F9, 0x7FC00000];
+        let output = launch("cvt.rzi.s32.f32 y, x;", &input, 1);
+        assert_eq!(output, [0xFFFFFFFE, 2, 0x7FFFFFFF, 0]);
+        let output = launch("cvt.rni.s32.f32 y, x;", &input, 1);
+        assert_eq!(output, [0xFFFFFFFE, 2, 0x7FFFFFFF, 0]);
+        let output = launch("cvt.rmi.s32.f32 y, x;", &input, 1);
+        assert_eq!(output, [0xFFFFFFFD, 2, 0x7FFFFFFF, 0]);
+    }
+
+    #[test]
+    fn setp_and_selp() {
+        let body = ".reg .pred p;\nsetp.lt.s32 p, x, 0;\nselp.u32 y, 1, 2, p;";
+        let output = launch(body, &[0xFFFFFFFF, 0, 5], 1);
+        assert_eq!(output, [1, 2, 2]);
+    }
+
+    #[test]
+    fn setp_unsigned_compares() {
+        let input = [0, 5, 6, 0xFFFFFFFF];
+        for (cmp, expected) in [
+            ("lo", [1, 2, 2, 2]),
+            ("ls", [1, 1, 2, 2]),
+            ("hi", [2, 2, 1, 1]),
+            ("hs", [2, 1, 1, 1]),
+        ] {
+            let body = format!(".reg .pred p;\nsetp.{cmp}.u32 p, x, 5;\nselp.u32 y, 1, 2, p;");
+            assert_eq!(launch(&body, &input, 1), expected, "setp.{cmp}");
+        }
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        let body = "// line comment
+            add.u32 y, /* block */ x, 1; /* multi-line
+            add.u32 y, y, 1; */
+            add.u32/**/y, y, 2; // trailing";
+        assert_eq!(launch(body, &[1, 2], 1), [4, 5]);
+    }
+
+    #[test]
+    fn unsupported_instructions_are_reported() {
+        assert_eq!(unsupported(&module("mov.b32 y, x;")), None);
+        assert_eq!(
+            unsupported(&module("sin.approx.f32 y, x;")).as_deref(),
+            Some("unsupported instruction `sin.approx.f32`")
+        );
+        assert_eq!(
+            unsupported(&module("add.rz.f32 y, x, x;")).as_deref(),
+            Some("unsupported instruction `add.rz.f32`")
+        );
+    }
+
+    #[test]
+    fn invalid_ptx_fails_to_load() {
+        let ptx = module("mov.b32 y, z;");
+        assert_eq!(unsupported(&ptx), None);
+        let err = Interpreter::new()
+            .load_module(&CString::new(ptx).unwrap())
+            .unwrap_err();
+        assert_eq!(err.name, "CUDA_ERROR_INVALID_PTX");
+        assert_eq!(err.description, "unknown operand `z`");
+    }
+}
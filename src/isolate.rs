@@ -0,0 +1,105 @@
+use std::{
+    env, fs,
+    io::Read,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{report::Report, test::TestError};
+
+/// How much of the child's stderr is kept when it crashes.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// How often a running child is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run a single test in a child process executing this binary with `args`.
+///
+/// The child reports its result through a JSON report, if it exits without writing one, the test
+/// is considered crashed. A child still running after `timeout` is killed, this is the only
+/// reliable way to recover from a kernel that never finishes.
+pub fn run_in_child(
+    name: &str,
+    args: &[String],
+    timeout: Option<Duration>,
+) -> Result<(), TestError> {
+    let report_path = env::temp_dir().join(format!("ptx_tests_{}_{name}.json", std::process::id()));
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args(args)
+        .arg("--filter")
+        .arg(format!("^{}$", regex::escape(name)))
+        .arg("--report")
+        .arg(&report_path)
+        .arg("--child")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Drain stderr concurrently, a child blocked on a full pipe would never exit
+    let mut stderr_pipe = child.stderr.take().unwrap();
+    let stderr = thread::spawn(move || {
+        let mut stderr = Vec::new();
+        let _ = stderr_pipe.read_to_end(&mut stderr);
+        stderr
+    });
+    let status = wait(&mut child, timeout);
+    if status.is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    let stderr = stderr.join().unwrap();
+    let Some(status) = status else {
+        let _ = fs::remove_file(&report_path);
+        return Err(TestError::Timeout {
+            seconds: timeout.unwrap_or_default().as_secs_f64(),
+        });
+    };
+    let report = fs::read(&report_path)
+        .ok()
+        .and_then(|report| serde_json::from_slice::<Report>(&report).ok());
+    let _ = fs::remove_file(&report_path);
+
+    match report.and_then(|report| report.tests.into_iter().next()) {
+        Some(record) => record.error.map_or(Ok(()), Err),
+        None => Err(TestError::Crashed {
+            signal: signal(status),
+            stderr_tail: tail(&stderr, STDERR_TAIL_LINES),
+        }),
+    }
+}
+
+/// Wait for the child to exit, returns `None` if it is still running after `timeout`.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> Option<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return Some(child.wait().unwrap());
+    };
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return Some(status);
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return None;
+        }
+        thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+#[cfg(unix)]
+pub fn signal(status: ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(&status)
+}
+
+#[cfg(not(unix))]
+pub fn signal(_: ExitStatus) -> Option<i32> {
+    None
+}
+
+pub fn tail(output: &[u8], lines: usize) -> String {
+    let output = String::from_utf8_lossy(output);
+    let all_lines: Vec<_> = output.lines().collect();
+    all_lines[all_lines.len().saturating_sub(lines)..].join("\n")
+}
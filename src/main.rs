@@ -3,51 +3,226 @@
 #![feature(f16)]
 #![feature(c_size_t)]
 
-use std::ptr;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
 
 use bpaf::Bpaf;
 use nvrtc::Nvrtc;
 use regex::{self, Regex};
 
 use cuda::Cuda;
-use test::{TestCase, TestError};
+use driver::Driver;
+use expectations::Expectations;
+use golden::{golden_path, Golden};
+use interpreter::Interpreter;
+use metadata::{has_tag, parse_conditions, Condition, PtxVersion, Strategy};
+use report::{Comparison, Report, RunHeader, TestRecord, TestStatus};
+use test::{TestCase, TestError, TestOptions, DEFAULT_SEED, GROUP_SIZE};
 use testcase::*;
 
 mod common;
 mod cuda;
+mod driver;
+mod expectations;
+mod golden;
+mod interpreter;
+mod isolate;
+mod metadata;
 mod nvrtc;
+mod parallel;
+mod report;
+mod spec;
 mod test;
 mod testcase;
 
 #[derive(Debug, Clone, Bpaf)]
 #[bpaf(options)]
+struct Options {
+    /// load additional tests from a TOML file or a directory of TOML files, can be repeated
+    #[bpaf(argument("PATH"))]
+    spec: Vec<PathBuf>,
+
+    #[bpaf(external)]
+    target_options: TargetOptions,
+
+    #[bpaf(external)]
+    arguments: Arguments,
+}
+
+#[derive(Debug, Clone, Bpaf)]
 enum Arguments {
     List {
-        /// list all available tests, execute no tests
+        /// list all available tests, execute no tests. Test selection options narrow down the list
         #[bpaf(short, long)]
         #[allow(dead_code)]
         list: (),
+
+        #[bpaf(external, hide)]
+        selection: Selection,
     },
-    Run {
-        /// only tests matching this regex will be executed
-        #[bpaf(short, long)]
-        filter: Option<String>,
+    Run(#[bpaf(external(run_args), map(Box::new))] Box<RunArgs>),
+    /// Run tests on two CUDA libraries and compare their outputs bit for bit
+    #[bpaf(command)]
+    Diff {
+        #[bpaf(external)]
+        selection: Selection,
 
         /// path to NVRTC shared library, switches to testing inline PTX embedded in CUDA sources when provided
         #[bpaf(long)]
         nvrtc: Option<String>,
 
-        /// number of shards to split the tests into for parallel execution
-        #[bpaf(external, optional)]
-        shards: Option<Shards>,
+        #[bpaf(external(run_options))]
+        options: RunOptions,
+
+        /// index of the CUDA device to run tests on with both libraries, defaults to 0
+        #[bpaf(long("device"), argument("N"), fallback(0))]
+        device: usize,
 
-        /// fail on the first test failure
-        fail_fast: bool,
+        /// path to the first CUDA shared library, A in the results
+        #[bpaf(positional("cuda_a"))]
+        cuda_a: String,
 
-        /// path to CUDA shared library under testing, for example C:\Windows\System32\nvcuda.dll or /usr/lib/x86_64-linux-gnu/libcuda.so
+        /// path to the second CUDA shared library, B in the results
+        #[bpaf(positional("cuda_b"))]
+        cuda_b: String,
+    },
+    /// Verify outputs recorded with --record on the host, no GPU is needed
+    #[bpaf(command)]
+    Replay {
+        #[bpaf(external)]
+        selection: Selection,
+
+        #[bpaf(external(run_options))]
+        options: RunOptions,
+
+        /// the outputs were recorded with --nvrtc
+        nvrtc: bool,
+
+        /// directory with outputs recorded with --record
+        #[bpaf(positional("DIR"))]
+        dir: PathBuf,
+    },
+    /// Compare two reports written with --report and print the tests that changed
+    #[bpaf(command)]
+    Compare {
+        /// report of the earlier run
+        #[bpaf(positional("BASE"))]
+        base: PathBuf,
+
+        /// report of the run to compare against BASE
+        #[bpaf(positional("NEW"))]
+        new: PathBuf,
+    },
+    /// Launch a single test on explicit inputs and print the GPU and host results
+    #[bpaf(command)]
+    Repro {
+        /// path to NVRTC shared library, switches to testing inline PTX embedded in CUDA sources when provided
+        #[bpaf(long)]
+        nvrtc: Option<String>,
+
+        /// index of the CUDA device to launch the test on, defaults to 0
+        #[bpaf(long("device"), argument("N"), fallback(0))]
+        device: usize,
+
+        /// path to CUDA shared library under testing, or `interpreter` to execute PTX on the host
         #[bpaf(positional("cuda"))]
         cuda: String,
+
+        /// name of the test to launch
+        #[bpaf(positional("test"))]
+        test: String,
+
+        /// input as hex bit patterns of its components separated by commas, for example 0x3f800000,0x7f800000
+        #[bpaf(positional("INPUT"), some("at least one input is required"))]
+        inputs: Vec<String>,
     },
+    /// Print the source a test compiles, no CUDA library is needed
+    #[bpaf(command)]
+    Show {
+        /// print the CUDA source with inline PTX compiled by NVRTC instead of the PTX module
+        nvrtc: bool,
+
+        /// name of the test to show
+        #[bpaf(positional("test"))]
+        test: String,
+    },
+}
+
+// Arguments of the default command running the tests, boxed because they are much larger than
+// the arguments of other commands
+#[derive(Debug, Clone, Bpaf)]
+struct RunArgs {
+    #[bpaf(external)]
+    selection: Selection,
+
+    /// path to NVRTC shared library, switches to testing inline PTX embedded in CUDA sources when provided
+    #[bpaf(long)]
+    nvrtc: Option<String>,
+
+    /// number of shards to split the tests into for parallel execution
+    #[bpaf(external, optional)]
+    shards: Option<Shards>,
+
+    #[bpaf(external, optional)]
+    golden_dir: Option<GoldenDir>,
+
+    #[bpaf(external, fallback(Devices::Device { device: 0 }))]
+    devices: Devices,
+
+    #[bpaf(external(run_options))]
+    options: RunOptions,
+
+    /// run every test in a separate child process, so that a crashing test does not take down the whole run
+    isolate: bool,
+
+    /// kill tests running for longer than this, implies --isolate unless --parallel is given. Tests known to run long have their own timeout, which overrides this and always runs them in a child process
+    #[bpaf(argument("SECONDS"))]
+    timeout: Option<u64>,
+
+    /// TOML file listing tests that are known to fail or should be skipped, with a reason for each
+    #[bpaf(argument("PATH"))]
+    expectations: Option<PathBuf>,
+
+    /// run tests in this many worker processes at once, a crashing test only takes down its worker and is retried once. Combined with --devices all, workers are spread over all devices. Can't be combined with --isolate
+    #[bpaf(
+        argument("N"),
+        guard(|workers: &usize| *workers > 0, "must be at least 1"),
+        optional
+    )]
+    parallel: Option<usize>,
+
+    /// serve tests requested by a --parallel supervisor on stdin
+    #[bpaf(hide)]
+    worker: bool,
+
+    /// run a single test in a child process of --isolate, a test with its own timeout is not isolated again
+    #[bpaf(hide)]
+    child: bool,
+
+    /// with --nvrtc, run every test once more for each of the NVRTC options -use_fast_math, -ftz=true, -prec-div=false, -G and -lineinfo, in addition to --nvrtc-opt
+    nvrtc_matrix: bool,
+
+    /// write the source of every selected test into this directory as <test>.ptx, with --nvrtc also the CUDA source as <test>.cu and the PTX produced by NVRTC as <test>.nvrtc.ptx, for every option set of --nvrtc-matrix as <test> [<options>].nvrtc.ptx
+    #[bpaf(argument("DIR"))]
+    dump_sources: Option<PathBuf>,
+
+    /// write a JSON report with the results of all executed tests to this path
+    #[bpaf(long, argument("PATH"))]
+    report: Option<PathBuf>,
+
+    /// write the results of all executed tests as JUnit XML to this path
+    #[bpaf(long, argument("PATH"))]
+    junit: Option<PathBuf>,
+
+    /// path to CUDA shared library under testing, for example C:\Windows\System32\nvcuda.dll or /usr/lib/x86_64-linux-gnu/libcuda.so, or `interpreter` to execute PTX on the host
+    #[bpaf(positional("cuda"))]
+    cuda: String,
 }
 
 #[derive(Debug, Clone, Bpaf)]
@@ -58,95 +233,1080 @@ struct Shards {
     shard_count: usize,
 }
 
+#[derive(Debug, Clone, Bpaf)]
+enum Devices {
+    Device {
+        /// index of the CUDA device to run tests on, defaults to 0
+        #[bpaf(long("device"), argument("N"))]
+        device: usize,
+    },
+    All {
+        /// run tests on all CUDA devices at once, each device takes the next test from a shared queue
+        #[bpaf(
+            long("devices"),
+            argument("all"),
+            guard(|value: &String| value == "all", "the only supported value is `all`")
+        )]
+        #[allow(dead_code)]
+        devices: String,
+    },
+}
+
+impl Devices {
+    /// Indices of the selected devices, exits if a device does not exist.
+    fn indices(&self, driver: &dyn Driver) -> Vec<usize> {
+        let count = driver.device_count().unwrap();
+        match self {
+            Devices::Device { device } if *device < count => vec![*device],
+            Devices::Device { device } => {
+                eprintln!("Device {device} does not exist, found {count} devices");
+                std::process::exit(1);
+            }
+            Devices::All { .. } if count == 0 => {
+                eprintln!("No CUDA devices found");
+                std::process::exit(1);
+            }
+            Devices::All { .. } => (0..count).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Bpaf)]
+enum GoldenDir {
+    Record {
+        /// record the GPU outputs of every test into this directory
+        #[bpaf(long("record"), argument("DIR"))]
+        record: PathBuf,
+    },
+    Compare {
+        /// compare GPU outputs bit for bit against outputs recorded with --record into this directory
+        #[bpaf(long("compare-golden"), argument("DIR"))]
+        compare_golden: PathBuf,
+    },
+}
+
+impl GoldenDir {
+    fn for_test(&self, test: &str) -> Golden {
+        match self {
+            GoldenDir::Record { record } => Golden::Record(golden_path(record, test)),
+            GoldenDir::Compare { compare_golden } => {
+                Golden::Compare(golden_path(compare_golden, test))
+            }
+        }
+    }
+
+    fn to_args(&self) -> [String; 2] {
+        match self {
+            GoldenDir::Record { record } => ["--record".to_string(), record.display().to_string()],
+            GoldenDir::Compare { compare_golden } => [
+                "--compare-golden".to_string(),
+                compare_golden.display().to_string(),
+            ],
+        }
+    }
+}
+
+/// Test selection:
+#[derive(Debug, Clone, Bpaf)]
+struct Selection {
+    /// only tests matching this regex will be executed
+    #[bpaf(short, long)]
+    filter: Option<String>,
+
+    /// only tests with this tag will be executed, a tag is an instruction, operand type, modifier or strategy, for example `approx`. Can be given multiple times
+    #[bpaf(long("tag"), argument("TAG"), many)]
+    tags: Vec<String>,
+
+    /// only tests matching all of these comma separated conditions will be executed, for example `instr=cvt,rnd=rzi,from=f32`. Keys are instr, type, to, from, rnd, mod, ftz, sat, approx, strategy, sm and ptx, `sm=N` selects tests that can run on sm_N and `ptx=X.Y` tests that compile with PTX X.Y. Can be given multiple times
+    #[bpaf(
+        long("where"),
+        argument::<String>("CONDITIONS"),
+        parse(parse_conditions),
+        many
+    )]
+    conditions: Vec<Vec<Condition>>,
+}
+
+impl Selection {
+    fn apply(&self, tests: &mut Vec<TestCase>) {
+        if let Some(filter) = &self.filter {
+            let re = Regex::new(filter).unwrap();
+            tests.retain(|t| re.is_match(&t.name));
+        }
+        tests.retain(|t| {
+            self.tags.iter().all(|tag| has_tag(t, tag))
+                && self.conditions.iter().flatten().all(|c| c.matches(t))
+        });
+    }
+}
+
+/// PTX target:
+#[derive(Debug, Clone, Bpaf)]
+struct TargetOptions {
+    /// PTX ISA version in the .version directive of generated modules
+    #[bpaf(argument("X.Y"), fallback(PtxVersion(7, 8)), display_fallback)]
+    ptx_version: PtxVersion,
+
+    /// architecture in the .target directive of generated modules, for example sm_70 or sm_90a. Tests of instructions that need a newer architecture or PTX version are skipped
+    #[bpaf(
+        long("target"),
+        argument("SM"),
+        guard(|sm: &String| sm.starts_with("sm_") && arch_number(sm).is_some(), "expected sm_N"),
+        fallback("sm_90".to_string()),
+        display_fallback
+    )]
+    sm: String,
+
+    /// architecture passed to NVRTC as -arch, for example sm_75 or compute_75, defaults to --target
+    #[bpaf(
+        argument("ARCH"),
+        guard(|arch: &String| arch_number(arch).is_some(), "expected sm_N or compute_N"),
+        optional
+    )]
+    nvrtc_arch: Option<String>,
+
+    /// option passed to NVRTC, for example --nvrtc-opt=-use_fast_math. Can be given multiple times
+    #[bpaf(argument("OPT"), many)]
+    nvrtc_opt: Vec<String>,
+}
+
+impl TargetOptions {
+    fn target(&self) -> Target {
+        Target {
+            ptx_version: self.ptx_version,
+            sm: self.sm.clone(),
+            nvrtc_arch: self.nvrtc_arch.clone().unwrap_or_else(|| self.sm.clone()),
+            nvrtc_options: self.nvrtc_opt.clone(),
+        }
+    }
+
+    /// Command line arguments that reproduce these options.
+    fn to_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--ptx-version".to_string(),
+            self.ptx_version.to_string(),
+            "--target".to_string(),
+            self.sm.clone(),
+        ];
+        if let Some(arch) = &self.nvrtc_arch {
+            args.extend(["--nvrtc-arch".to_string(), arch.clone()]);
+        }
+        // Options start with a dash, so they have to be attached to the flag
+        args.extend(
+            self.nvrtc_opt
+                .iter()
+                .map(|option| format!("--nvrtc-opt={option}")),
+        );
+        args
+    }
+}
+
+// Options controlling the execution of each test, `--isolate` forwards them to child processes
+/// Test execution options:
+#[derive(Debug, Clone, Bpaf)]
+struct RunOptions {
+    /// fail on the first test failure
+    fail_fast: bool,
+
+    /// maximum number of failing cases reported for each test, evenly spread over all failures
+    #[bpaf(argument("N"), fallback(16))]
+    max_samples: usize,
+
+    /// number of threads used for host-side verification, defaults to the number of CPU cores
+    #[bpaf(short, long, argument("N"))]
+    jobs: Option<usize>,
+
+    /// seed of the random number generator used by tests with random inputs
+    #[bpaf(argument("SEED"), fallback(DEFAULT_SEED), display_fallback)]
+    seed: u64,
+
+    /// number of cases generated by tests with random inputs, overrides the default of each test
+    #[bpaf(
+        argument("N"),
+        guard(valid_case_count, "must be a non-zero multiple of 128"),
+        optional
+    )]
+    random_cases: Option<usize>,
+}
+
+/// Sets of NVRTC options of `--nvrtc-matrix`, options that real applications commonly compile with
+const NVRTC_MATRIX: [&[&str]; 6] = [
+    &[],
+    &["-use_fast_math"],
+    &["-ftz=true"],
+    &["-prec-div=false"],
+    &["-G"],
+    &["-lineinfo"],
+];
+
+fn valid_case_count(cases: &usize) -> bool {
+    *cases != 0 && cases.is_multiple_of(GROUP_SIZE)
+}
+
+impl RunOptions {
+    fn test_options(&self) -> TestOptions {
+        TestOptions {
+            fail_fast: self.fail_fast,
+            max_samples: self.max_samples,
+            jobs: self
+                .jobs
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get())),
+            seed: self.seed,
+            random_cases: self.random_cases,
+            golden: None,
+        }
+    }
+
+    /// Command line arguments that reproduce these options.
+    fn to_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--max-samples".to_string(),
+            self.max_samples.to_string(),
+            "--seed".to_string(),
+            self.seed.to_string(),
+        ];
+        if self.fail_fast {
+            args.push("--fail-fast".to_string());
+        }
+        if let Some(jobs) = self.jobs {
+            args.extend(["--jobs".to_string(), jobs.to_string()]);
+        }
+        if let Some(cases) = self.random_cases {
+            args.extend(["--random-cases".to_string(), cases.to_string()]);
+        }
+        args
+    }
+}
+
 fn main() {
-    let args = arguments().run();
+    let Options {
+        spec,
+        target_options,
+        arguments: args,
+    } = options().run();
+    let target = target_options.target();
 
-    let mut tests = tests();
+    let mut tests = load_tests(&spec);
 
     match args {
-        Arguments::List { .. } => {
+        Arguments::List { selection, .. } => {
+            selection.apply(&mut tests);
             for test in tests {
                 println!("{}", test.name);
             }
         }
-        Arguments::Run {
-            filter,
+        Arguments::Run(run_args) => {
+            let RunArgs {
+                selection,
+                nvrtc,
+                cuda,
+                shards,
+                golden_dir,
+                devices,
+                options,
+                isolate,
+                timeout,
+                expectations,
+                parallel,
+                worker,
+                child,
+                nvrtc_matrix,
+                dump_sources,
+                report,
+                junit,
+            } = *run_args;
+            selection.apply(&mut tests);
+            let mut tests = if let Some(shards) = shards {
+                shard_tests(tests, &shards, &options.test_options())
+            } else {
+                tests
+            };
+            let option_sets: &[&[&str]] = if nvrtc_matrix { &NVRTC_MATRIX } else { &[&[]] };
+            if let Some(dir) = dump_sources {
+                let nvrtc = nvrtc.clone().map(Nvrtc::new);
+                write_sources(&tests, &dir, &target, nvrtc.as_ref(), option_sets);
+            }
+            let expectations = match expectations {
+                Some(path) => Expectations::load(&path).unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }),
+                None => Expectations::default(),
+            };
+            let mut skipped = Vec::new();
+            tests.retain(|t| {
+                // Workers only get tests from the supervisor, which already skipped the rest
+                let skip = expectations
+                    .skipped(&t.name)
+                    .or_else(|| unsupported(t, &target, nvrtc.is_some(), cuda == INTERPRETER))
+                    .filter(|_| !worker);
+                match skip {
+                    Some(record) => {
+                        print_result(&record);
+                        skipped.push(record);
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            let header = RunHeader {
+                cuda: cuda.clone(),
+                nvrtc: nvrtc.clone(),
+                arguments: std::env::args().collect(),
+            };
+
+            // Arguments of child processes running the tests, except the device and CUDA library
+            let mut args = options.to_args();
+            for path in &spec {
+                args.extend(["--spec".to_string(), path.display().to_string()]);
+            }
+            args.extend(target_options.to_args());
+            if let Some(nvrtc) = &nvrtc {
+                args.extend(["--nvrtc".to_string(), nvrtc.clone()]);
+            }
+            if let Some(golden_dir) = &golden_dir {
+                args.extend(golden_dir.to_args());
+            }
+            let timeout = timeout.map(Duration::from_secs);
+
+            if parallel.is_some() && isolate {
+                eprintln!("--isolate can't be combined with --parallel, workers already run tests in separate processes");
+                std::process::exit(1);
+            }
+            if nvrtc_matrix && nvrtc.is_none() {
+                eprintln!("--nvrtc-matrix requires --nvrtc");
+                std::process::exit(1);
+            }
+            if nvrtc_matrix && golden_dir.is_some() {
+                eprintln!("--nvrtc-matrix can't be combined with --record or --compare-golden");
+                std::process::exit(1);
+            }
+            // Tests are consumed by a run, every further set of options runs a fresh copy
+            let names: HashSet<_> = tests.iter().map(|t| t.name.clone()).collect();
+            let mut tests = Some(tests);
+            let mut results = skipped;
+            for extra in option_sets {
+                let tests = tests.take().unwrap_or_else(|| {
+                    let mut tests = load_tests(&spec);
+                    tests.retain(|t| names.contains(&t.name));
+                    tests
+                });
+                let mut target = target.clone();
+                target
+                    .nvrtc_options
+                    .extend(extra.iter().map(|option| option.to_string()));
+                let mut args = args.clone();
+                args.extend(extra.iter().map(|option| format!("--nvrtc-opt={option}")));
+                if nvrtc_matrix {
+                    let options: String = target
+                        .nvrtc_options
+                        .iter()
+                        .map(|option| format!(" {option}"))
+                        .collect();
+                    println!("NVRTC options: -arch={}{options}", target.nvrtc_arch);
+                }
+
+                let set_results = if let Some(parallel) = parallel {
+                    let devices = devices.indices(&*load_driver(cuda.clone()));
+                    let workers: Vec<_> = (0..parallel)
+                        .map(|worker| {
+                            let device = devices[worker % devices.len()];
+                            let mut args = args.clone();
+                            args.extend([
+                                "--device".to_string(),
+                                device.to_string(),
+                                "--worker".to_string(),
+                                cuda.clone(),
+                            ]);
+                            args
+                        })
+                        .collect();
+                    let tests = tests
+                        .iter()
+                        .map(|t| (t.name.clone(), t.timeout.or(timeout)))
+                        .collect();
+                    parallel::run_in_workers(tests, &workers, |record| {
+                        let record = expectations.apply(record);
+                        print_result(&record);
+                        record
+                    })
+                } else if isolate || timeout.is_some() {
+                    // Tests still run one at a time, every child gets a single device in turn so
+                    // that it doesn't create a context on every device for a single test
+                    let devices = devices.indices(&*load_driver(cuda.clone()));
+                    let mut devices = devices.iter().cycle();
+                    run_tests(tests, &expectations, |t| {
+                        let mut args = args.clone();
+                        args.extend([
+                            "--device".to_string(),
+                            devices.next().unwrap().to_string(),
+                            cuda.clone(),
+                        ]);
+                        isolate::run_in_child(&t.name, &args, t.timeout.or(timeout))
+                    })
+                } else {
+                    let opts = options.test_options();
+                    let driver = load_driver(cuda.clone());
+                    let nvrtc = nvrtc.clone().map(Nvrtc::new);
+                    let devices = devices.indices(&*driver);
+                    args.extend(["--device".to_string(), devices[0].to_string(), cuda.clone()]);
+                    let execution = if worker {
+                        Execution::Worker
+                    } else {
+                        // A child already is the process running its test
+                        Execution::Local {
+                            child_args: (!child).then_some(&args[..]),
+                        }
+                    };
+                    let cuda = driver;
+
+                    if let Some(nvrtc) = nvrtc {
+                        let libs = (cuda, nvrtc);
+                        let ctx = TestFixture {
+                            libs,
+                            target: target.clone(),
+                        };
+                        let golden_dir = golden_dir.as_ref();
+                        run(
+                            tests,
+                            ctx,
+                            &opts,
+                            golden_dir,
+                            &devices,
+                            &expectations,
+                            execution,
+                        )
+                    } else {
+                        let libs = (cuda,);
+                        let ctx = TestFixture {
+                            libs,
+                            target: target.clone(),
+                        };
+                        let golden_dir = golden_dir.as_ref();
+                        run(
+                            tests,
+                            ctx,
+                            &opts,
+                            golden_dir,
+                            &devices,
+                            &expectations,
+                            execution,
+                        )
+                    }
+                };
+                // Results of the matrix are reported separately for every set of options
+                results.extend(set_results.into_iter().map(|mut record| {
+                    if !extra.is_empty() {
+                        record.name = format!("{} [{}]", record.name, extra.join(" "));
+                    }
+                    record
+                }));
+            }
+            if parallel.is_some() || !expectations.is_empty() || nvrtc_matrix {
+                print_summary(&results);
+            }
+
+            let failures = results.iter().filter(|r| r.failed()).count();
+
+            let results = Report {
+                header,
+                tests: results,
+            };
+            if let Some(path) = report {
+                results.write(&path).unwrap();
+            }
+            if let Some(path) = junit {
+                results.write_junit(&path).unwrap();
+            }
+
+            std::process::exit(failures as i32);
+        }
+        Arguments::Diff {
+            selection,
             nvrtc,
-            cuda,
-            shards,
-            fail_fast,
+            options,
+            device,
+            cuda_a,
+            cuda_b,
         } => {
-            if let Some(filter) = filter {
-                let re = Regex::new(&filter).unwrap();
-                tests = tests.into_iter().filter(|t| re.is_match(&t.name)).collect();
-            }
-            let tests = if let Some(shards) = shards {
-                let start = shards.shard_index * tests.len() / shards.shard_count;
-                let end = (shards.shard_index + 1) * tests.len() / shards.shard_count;
-                tests.drain(start..end).collect()
+            selection.apply(&mut tests);
+            let interpreter = cuda_a == INTERPRETER || cuda_b == INTERPRETER;
+            tests.retain(
+                |t| match unsupported(t, &target, nvrtc.is_some(), interpreter) {
+                    Some(record) => {
+                        print_result(&record);
+                        false
+                    }
+                    None => true,
+                },
+            );
+            let opts = options.test_options();
+            let cuda_a = load_driver(cuda_a);
+            let cuda_b = load_driver(cuda_b);
+
+            let failures = if let Some(nvrtc) = nvrtc {
+                let a = TestFixture {
+                    libs: (cuda_a, Nvrtc::new(nvrtc.clone())),
+                    target: target.clone(),
+                };
+                let b = TestFixture {
+                    libs: (cuda_b, Nvrtc::new(nvrtc)),
+                    target,
+                };
+                diff(tests, [&a, &b], &opts, device)
             } else {
-                tests
+                let a = TestFixture {
+                    libs: (cuda_a,),
+                    target: target.clone(),
+                };
+                let b = TestFixture {
+                    libs: (cuda_b,),
+                    target,
+                };
+                diff(tests, [&a, &b], &opts, device)
             };
 
-            let cuda = Cuda::new(cuda);
+            std::process::exit(failures as i32);
+        }
+        Arguments::Replay {
+            selection,
+            options,
+            nvrtc,
+            dir,
+        } => {
+            selection.apply(&mut tests);
+            let opts = options.test_options();
+            let expectations = Expectations::default();
+            let results: Vec<_> = tests
+                .into_iter()
+                .map(|t| {
+                    let start = Instant::now();
+                    // Only invalid tests have more than one PTX, they have nothing to replay
+                    let source = match t.test.ptx()[..] {
+                        [(_, ptx)] => golden_source(ptx, &target, nvrtc),
+                        _ => String::new(),
+                    };
+                    let record = match t.test.replay(&golden_path(&dir, &t.name), &opts, &source) {
+                        Some(result) => {
+                            expectations.apply(TestRecord::new(t.name, result, start.elapsed()))
+                        }
+                        None => skipped(t.name, "no recorded outputs to replay".to_string()),
+                    };
+                    print_result(&record);
+                    record
+                })
+                .collect();
+            let failures = results.iter().filter(|r| r.failed()).count();
+            std::process::exit(failures as i32);
+        }
+        Arguments::Compare { base, new } => {
+            let [base, new] = [base, new].map(|path| {
+                Report::read(&path).unwrap_or_else(|err| {
+                    eprintln!("{}: {err}", path.display());
+                    std::process::exit(1);
+                })
+            });
+            let regressions = compare(&base, &new);
+            std::process::exit(regressions as i32);
+        }
+        Arguments::Repro {
+            nvrtc,
+            device,
+            cuda,
+            test,
+            inputs,
+        } => {
+            let Some(test) = tests.into_iter().find(|t| t.name == test) else {
+                eprintln!("Unknown test {test}");
+                std::process::exit(1);
+            };
+            if let Some(record) = unsupported(&test, &target, nvrtc.is_some(), cuda == INTERPRETER)
+            {
+                eprintln!("{}: {}", test.name, record.reason.unwrap());
+                std::process::exit(1);
+            }
+            let inputs: Vec<_> = inputs.iter().map(|input| parse_input(input)).collect();
+            let cuda = load_driver(cuda);
             let nvrtc = nvrtc.map(Nvrtc::new);
 
-            let failures = if let Some(nvrtc) = nvrtc {
+            let result = if let Some(nvrtc) = nvrtc {
                 let libs = (cuda, nvrtc);
-                run(tests, TestFixture { libs }, fail_fast)
+                repro(&test, TestFixture { libs, target }, &inputs, device)
             } else {
                 let libs = (cuda,);
-                run(tests, TestFixture { libs }, fail_fast)
+                repro(&test, TestFixture { libs, target }, &inputs, device)
             };
+            if let Err(err) = result {
+                eprintln!("{}: {err}", test.name);
+                std::process::exit(1);
+            }
+        }
+        Arguments::Show { nvrtc, test } => {
+            let Some(test) = tests.into_iter().find(|t| t.name == test) else {
+                eprintln!("Unknown test {test}");
+                std::process::exit(1);
+            };
+            for (name, ptx) in test.test.ptx() {
+                if let Some(name) = name {
+                    println!("// {name}");
+                }
+                if nvrtc {
+                    println!("{}", cuda_source(ptx));
+                } else {
+                    println!("{}", ptx_source(ptx, &target));
+                }
+            }
+        }
+    }
+}
 
-            std::process::exit(failures);
+/// Built-in tests followed by the tests of every `--spec`, exits if a spec is invalid.
+fn load_tests(spec: &[PathBuf]) -> Vec<TestCase> {
+    let mut tests = tests();
+    // Specs can only name built-in tests as their reference
+    let references: HashMap<_, _> = tests
+        .iter()
+        .map(|t| (t.name.clone(), t.test.clone()))
+        .collect();
+    for path in spec {
+        let specs = spec::load(path, &references).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        });
+        for t in specs {
+            if tests.iter().any(|existing| existing.name == t.name) {
+                eprintln!("{}: test {} already exists", path.display(), t.name);
+                std::process::exit(1);
+            }
+            tests.push(t);
         }
     }
+    tests
 }
 
-fn run(tests: Vec<TestCase>, ctx: impl TestContext, fail_fast: bool) -> i32 {
-    let cuda = ctx.cuda();
+/// Parse comma separated hex bit patterns, one for every component of a test input.
+fn parse_input(input: &str) -> Vec<u64> {
+    input
+        .split(',')
+        .map(|component| {
+            let component = component.trim();
+            let digits = component
+                .strip_prefix("0x")
+                .or_else(|| component.strip_prefix("0X"))
+                .unwrap_or(component);
+            u64::from_str_radix(digits, 16).unwrap_or_else(|_| {
+                eprintln!("Invalid hex value {component} in input {input}");
+                std::process::exit(1);
+            })
+        })
+        .collect()
+}
 
-    let mut failures = 0;
+/// Select the tests of a single shard. Tests are assigned to shards by greedy bin-packing of
+/// their estimated costs, the most expensive test goes to the shard with the lowest total cost
+/// first. Ties are broken by name and shard index, so every shard computes the same assignment.
+fn shard_tests(tests: Vec<TestCase>, shards: &Shards, opts: &TestOptions) -> Vec<TestCase> {
+    let mut order: Vec<_> = (0..tests.len()).collect();
+    let costs: Vec<_> = tests.iter().map(|t| t.test.cost(opts)).collect();
+    order.sort_by(|&a, &b| {
+        costs[b]
+            .cmp(&costs[a])
+            .then_with(|| tests[a].name.cmp(&tests[b].name))
+    });
+    let mut loads = vec![0u64; shards.shard_count];
+    let mut selected = vec![false; tests.len()];
+    for index in order {
+        let shard = (0..shards.shard_count)
+            .min_by_key(|&shard| (loads[shard], shard))
+            .unwrap();
+        loads[shard] += costs[index];
+        selected[index] = shard == shards.shard_index;
+    }
+    // Keep the usual order of tests within a shard
+    tests
+        .into_iter()
+        .zip(selected)
+        .filter_map(|(t, selected)| selected.then_some(t))
+        .collect()
+}
+
+/// Write the sources of `tests` into `dir`, see `--dump-sources`. NVRTC output is written for
+/// every set of extra NVRTC options, named after the options like the results of `--nvrtc-matrix`.
+fn write_sources(
+    tests: &[TestCase],
+    dir: &Path,
+    target: &Target,
+    nvrtc: Option<&Nvrtc>,
+    option_sets: &[&[&str]],
+) {
+    fs::create_dir_all(dir).unwrap();
+    for t in tests {
+        for (name, ptx) in t.test.ptx() {
+            let name = name.unwrap_or(&t.name);
+            fs::write(
+                dir.join(format!("{name}.ptx")),
+                ptx_source(ptx, target) + "\n",
+            )
+            .unwrap();
+            let Some(nvrtc) = nvrtc else {
+                continue;
+            };
+            let source = cuda_source(ptx);
+            fs::write(dir.join(format!("{name}.cu")), source.clone() + "\n").unwrap();
+            for extra in option_sets {
+                let mut target = target.clone();
+                target
+                    .nvrtc_options
+                    .extend(extra.iter().map(|option| option.to_string()));
+                let name = if extra.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{name} [{}]", extra.join(" "))
+                };
+                match compile_cuda(nvrtc, &source, &target) {
+                    Ok(ptx) => {
+                        fs::write(dir.join(format!("{name}.nvrtc.ptx")), ptx.as_bytes()).unwrap()
+                    }
+                    Err(err) => eprintln!("{name}: {err}"),
+                }
+            }
+        }
+    }
+}
+
+/// Skip record of a test that can't be compiled for `target`, or that uses PTX the interpreter
+/// can't execute when `interpreter` is set.
+fn unsupported(
+    test: &TestCase,
+    target: &Target,
+    nvrtc: bool,
+    interpreter: bool,
+) -> Option<TestRecord> {
+    let reason = target.unsupported(&test.metadata, nvrtc).or_else(|| {
+        if !interpreter {
+            return None;
+        }
+        // The interpreter only checks that modifiers make sense for an instruction, so it
+        // accepts some of the PTX that real drivers have to reject
+        if test.test.strategy() == Strategy::Invalid {
+            return Some("the interpreter does not reject all invalid PTX".to_string());
+        }
+        test.test
+            .ptx()
+            .into_iter()
+            .find_map(|(_, ptx)| interpreter::unsupported(&ptx_source(ptx, target)))
+            .map(|reason| format!("interpreter: {reason}"))
+    })?;
+    Some(skipped(test.name.clone(), reason))
+}
+
+fn skipped(name: String, reason: String) -> TestRecord {
+    TestRecord {
+        name,
+        status: TestStatus::Skip,
+        duration_secs: 0.0,
+        error: None,
+        reason: Some(reason),
+    }
+}
 
-    unsafe { cuda.cuInit(0) }.unwrap();
-    let mut cuda_ctx = ptr::null_mut();
-    unsafe { cuda.cuCtxCreate_v2(&mut cuda_ctx, 0, 0) }.unwrap();
+/// Path of the CUDA library that selects the host-side PTX interpreter instead
+const INTERPRETER: &str = "interpreter";
 
+/// Load the CUDA library at `path`, `interpreter` selects the host-side PTX interpreter instead.
+fn load_driver(path: String) -> Box<dyn Driver> {
+    if path == INTERPRETER {
+        Box::new(Interpreter::new())
+    } else {
+        Box::new(Cuda::new(path))
+    }
+}
+
+fn create_context(driver: &dyn Driver, device: usize) {
+    driver.init(device).unwrap();
+}
+
+/// Where `run` executes the tests of this process.
+#[derive(Clone, Copy)]
+enum Execution<'a> {
+    /// A worker of --parallel, tests come from and results go to the supervisor
+    Worker,
+    /// Tests run in this process in order, except tests with their own timeout, which run in a
+    /// child process started with `child_args`. `None` in a child process of --isolate
+    Local { child_args: Option<&'a [String]> },
+}
+
+fn run(
+    tests: Vec<TestCase>,
+    ctx: impl TestContext + Sync,
+    opts: &TestOptions,
+    golden_dir: Option<&GoldenDir>,
+    devices: &[usize],
+    expectations: &Expectations,
+    execution: Execution,
+) -> Vec<TestRecord> {
+    let execute = |t: TestCase| {
+        if let (
+            Some(timeout),
+            Execution::Local {
+                child_args: Some(args),
+            },
+        ) = (t.timeout, execution)
+        {
+            return isolate::run_in_child(&t.name, args, Some(timeout));
+        }
+        let opts = TestOptions {
+            golden: golden_dir.map(|dir| dir.for_test(&t.name)),
+            ..opts.clone()
+        };
+        t.test.run(&ctx, &opts)
+    };
+
+    if let Execution::Worker = execution {
+        // Results are sent to the supervisor, which also writes the reports
+        create_context(ctx.driver(), devices[0]);
+        if let Err(err) = parallel::serve(tests, execute) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        Vec::new()
+    } else if let [device] = devices {
+        create_context(ctx.driver(), *device);
+        run_tests(tests, expectations, execute)
+    } else {
+        run_tests_on_devices(
+            tests,
+            devices,
+            expectations,
+            |device| create_context(ctx.driver(), device),
+            execute,
+        )
+    }
+}
+
+/// Compare the outputs of every test on two drivers, returns the number of tests that differ.
+fn diff(
+    tests: Vec<TestCase>,
+    ctxs: [&dyn TestContext; 2],
+    opts: &TestOptions,
+    device: usize,
+) -> usize {
+    for ctx in ctxs {
+        create_context(ctx.driver(), device);
+    }
+
+    let mut failures = 0;
     for t in tests {
-        use TestError::*;
-
-        let result = (t.test)(&ctx, fail_fast);
-        if result.is_err() {
-            failures += 1;
-        }
-
-        print!("{}: ", t.name);
-        match result {
-            Ok(()) => println!("OK"),
-            Err(CompilationFail { message }) => println!("FAIL - Compilation failed:\n{message}"),
-            Err(CompilationSuccess { name }) => {
-                println!("FAIL - Compilation mismatch, didn't expect '{name}' to compile")
-            }
-            Err(ResultMismatch {
-                input,
-                output,
-                expected,
-                total_cases,
-                passed_cases,
-            }) => {
-                let percent = (passed_cases as f32 / total_cases as f32) * 100f32;
+        let diff = match t.test.diff(ctxs, opts) {
+            Ok(diff) => diff,
+            Err(err) => {
+                println!("{}: FAIL - {err}", t.name);
+                failures += 1;
+                continue;
+            }
+        };
+        let [failures_a, failures_b] = diff.reference_failures;
+        let total = diff.total_cases;
+        if diff.differing_cases == 0 {
+            if failures_a == 0 {
+                println!("{}: SAME", t.name);
+            } else {
                 println!(
-                    "FAIL - with input {input}\n    computed on GPU: {output}\n    computed on CPU: {expected}\n    passed: {passed_cases} out of {total_cases} ({percent}%)"
-                )
+                    "{}: SAME - both fail the reference in {failures_a} out of {total} cases",
+                    t.name
+                );
             }
-            Err(MissingRunFunction) => println!("FAIL - Missing run function"),
+            continue;
+        }
+        failures += 1;
+        println!(
+            "{}: DIFF - outputs differ in {} out of {total} cases\n    A fails the reference in {failures_a} cases\n    B fails the reference in {failures_b} cases",
+            t.name, diff.differing_cases
+        );
+        for sample in diff.samples {
+            let verdict = |passes| {
+                if passes {
+                    "matches CPU"
+                } else {
+                    "differs from CPU"
+                }
+            };
+            println!(
+                "    case {}: input {}\n        computed on A: {} ({})\n        computed on B: {} ({})",
+                sample.index,
+                sample.input,
+                sample.outputs[0],
+                verdict(sample.passes[0]),
+                sample.outputs[1],
+                verdict(sample.passes[1])
+            );
         }
     }
-
     failures
 }
 
+/// Print the differences between two runs, returns the number of tests that got worse.
+fn compare(base: &Report, new: &Report) -> usize {
+    let comparison = Comparison::new(base, new);
+    let print_changes = |title: &str, changes: &[[&TestRecord; 2]]| {
+        if changes.is_empty() {
+            return;
+        }
+        println!("{title} ({}):", changes.len());
+        for [base, new] in changes {
+            println!("    {}: {} -> {}", new.name, base.outcome(), new.outcome());
+        }
+    };
+    let print_tests = |title: &str, tests: &[&TestRecord]| {
+        if tests.is_empty() {
+            return;
+        }
+        println!("{title} ({}):", tests.len());
+        for test in tests {
+            println!("    {}: {}", test.name, test.outcome());
+        }
+    };
+
+    print_changes("Newly failing", &comparison.newly_failing);
+    print_changes("Newly passing", &comparison.newly_passing);
+    print_changes("Pass rate changed", &comparison.pass_rate_changed);
+    print_tests("Only in base", &comparison.removed);
+    print_tests("Only in new", &comparison.added);
+    let regressions = comparison.regressions();
+    println!(
+        "{} tests compared, {regressions} regressions",
+        new.tests.len()
+    );
+    regressions
+}
+
+fn repro(
+    test: &TestCase,
+    ctx: impl TestContext,
+    inputs: &[Vec<u64>],
+    device: usize,
+) -> Result<(), String> {
+    create_context(ctx.driver(), device);
+
+    let repro = test.test.repro(&ctx, inputs)?;
+    println!("{}", repro.ptx);
+    for (input, case) in inputs.iter().zip(repro.cases) {
+        let raw: Vec<_> = input.iter().map(|bits| format!("{bits:#X}")).collect();
+        println!("\ncase {}:", raw.join(","));
+        println!(
+            "    input: {:?}\n        {}",
+            case.input.debug, case.input.rich
+        );
+        println!(
+            "    computed on GPU: {}\n        {}",
+            case.output.debug, case.output.rich
+        );
+        match case.expected {
+            Some(expected) => println!(
+                "    computed on CPU: {}\n        {}",
+                expected.debug, expected.rich
+            ),
+            None => println!("    computed on CPU: same as GPU"),
+        }
+    }
+    Ok(())
+}
+
+/// Execute tests one by one with `execute`, printing results as they come.
+fn run_tests(
+    tests: Vec<TestCase>,
+    expectations: &Expectations,
+    mut execute: impl FnMut(TestCase) -> Result<(), TestError>,
+) -> Vec<TestRecord> {
+    tests
+        .into_iter()
+        .map(|t| run_test(t, expectations, &mut execute))
+        .collect()
+}
+
+/// Execute tests on multiple devices at once, each device takes the next test from a shared
+/// queue. Results are printed as they come but returned in the order of `tests`.
+fn run_tests_on_devices(
+    tests: Vec<TestCase>,
+    devices: &[usize],
+    expectations: &Expectations,
+    init: impl Fn(usize) + Sync,
+    execute: impl Fn(TestCase) -> Result<(), TestError> + Sync,
+) -> Vec<TestRecord> {
+    let queue = Mutex::new(tests.into_iter().enumerate());
+    let results = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for &device in devices {
+            let (queue, results, init, execute) = (&queue, &results, &init, &execute);
+            scope.spawn(move || {
+                init(device);
+                loop {
+                    let Some((index, t)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let record = run_test(t, expectations, execute);
+                    results.lock().unwrap().push((index, record));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, record)| record).collect()
+}
+
+fn run_test(
+    t: TestCase,
+    expectations: &Expectations,
+    execute: impl FnOnce(TestCase) -> Result<(), TestError>,
+) -> TestRecord {
+    let name = t.name.clone();
+    let start = Instant::now();
+    let result = execute(t);
+    let record = expectations.apply(TestRecord::new(name, result, start.elapsed()));
+    print_result(&record);
+    record
+}
+
+fn print_result(record: &TestRecord) {
+    let name = &record.name;
+    let reason = record.reason.as_deref().unwrap_or_default();
+    match (record.status, &record.error) {
+        (TestStatus::Ok, _) => println!("{name}: OK"),
+        (TestStatus::Fail, Some(err)) => println!("{name}: FAIL - {err}"),
+        (TestStatus::Fail, None) => println!("{name}: FAIL"),
+        (TestStatus::Xfail, _) => println!("{name}: XFAIL - {reason}"),
+        (TestStatus::Xpass, _) => println!("{name}: XPASS - expected to fail: {reason}"),
+        (TestStatus::Skip, _) => println!("{name}: SKIP - {reason}"),
+    }
+}
+
+/// Print the totals of a run, followed by the failing tests and unexpected passes in their
+/// usual order. Useful when results were printed out of order or mixed with known failures.
+fn print_summary(results: &[TestRecord]) {
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    println!(
+        "\n{} tests, {} passed, {} failed, {} expected failures, {} unexpected passes, {} skipped",
+        results.len(),
+        count(TestStatus::Ok),
+        count(TestStatus::Fail),
+        count(TestStatus::Xfail),
+        count(TestStatus::Xpass),
+        count(TestStatus::Skip)
+    );
+    for record in results.iter().filter(|r| r.failed()) {
+        let status = if record.status == TestStatus::Xpass {
+            "XPASS"
+        } else {
+            "FAIL"
+        };
+        println!("    {status} {}", record.name);
+    }
+}
+
 #[macro_export]
 macro_rules! impl_library {
     ($($abi:literal fn $fn_name:ident( $($arg_id:ident : $arg_type:ty),* $(,)* ) -> $ret_type:ty);* $(;)*) => {
@@ -0,0 +1,217 @@
+use std::{fmt, str::FromStr};
+
+use crate::test::TestCase;
+
+/// How a test picks its inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Every value of the input range is checked
+    Range,
+    /// Inputs are generated from a seed
+    Random,
+    /// A set of instruction variants that must fail to compile
+    Invalid,
+}
+
+impl Strategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Strategy::Range => "range",
+            Strategy::Random => "random",
+            Strategy::Invalid => "invalid",
+        }
+    }
+}
+
+/// PTX ISA version of a module, as in `.version 7.8`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PtxVersion(pub u32, pub u32);
+
+impl FromStr for PtxVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (major, minor) = s
+            .split_once('.')
+            .ok_or_else(|| format!("expected major.minor, got {s}"))?;
+        match (major.parse(), minor.parse()) {
+            (Ok(major), Ok(minor)) => Ok(PtxVersion(major, minor)),
+            _ => Err(format!("expected major.minor, got {s}")),
+        }
+    }
+}
+
+impl fmt::Display for PtxVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.0, self.1)
+    }
+}
+
+/// Structured description of the instruction a test exercises, matched by `--tag` and `--where`.
+#[derive(Debug, Clone, Default)]
+pub struct TestMetadata {
+    /// Instruction mnemonic, e.g. `cvt`
+    pub instr: String,
+    /// Operand types in the order they appear in the instruction, e.g. `s16, f32` for
+    /// `cvt.rzi.s16.f32`
+    pub types: Vec<String>,
+    /// Modifiers without the leading dot, e.g. `rzi, ftz, sat`
+    pub modifiers: Vec<String>,
+    /// Lowest `sm` the instruction is available on, 0 if it is available everywhere
+    pub min_sm: u32,
+    /// Lowest PTX ISA version the instruction is available in
+    pub min_ptx: PtxVersion,
+}
+
+impl TestMetadata {
+    pub fn new(instr: &str) -> Self {
+        TestMetadata {
+            instr: instr.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn types<'a>(mut self, types: impl IntoIterator<Item = &'a str>) -> Self {
+        self.types.extend(types.into_iter().map(str::to_string));
+        self
+    }
+
+    /// Empty modifiers are ignored and leading dots are stripped, so both `Rounding::as_str()` and
+    /// `Rounding::as_ptx()` can be passed as they are.
+    pub fn modifiers<'a>(mut self, modifiers: impl IntoIterator<Item = &'a str>) -> Self {
+        self.modifiers.extend(
+            modifiers
+                .into_iter()
+                .map(|modifier| modifier.trim_start_matches('.'))
+                .filter(|modifier| !modifier.is_empty())
+                .map(str::to_string),
+        );
+        self
+    }
+
+    /// Add `modifier` only if `enabled`, for optional modifiers like `ftz`.
+    pub fn flag(self, modifier: &str, enabled: bool) -> Self {
+        if enabled {
+            self.modifiers([modifier])
+        } else {
+            self
+        }
+    }
+
+    pub fn min_sm(mut self, sm: u32) -> Self {
+        self.min_sm = sm;
+        self
+    }
+
+    pub fn min_ptx(mut self, major: u32, minor: u32) -> Self {
+        self.min_ptx = PtxVersion(major, minor);
+        self
+    }
+}
+
+const ROUNDINGS: [&str; 8] = ["rn", "rz", "rm", "rp", "rni", "rzi", "rmi", "rpi"];
+
+#[derive(Debug, Clone)]
+enum Key {
+    Instr,
+    Type,
+    /// First operand type, the destination
+    To,
+    /// Last operand type, the source
+    From,
+    Rnd,
+    Mod,
+    /// Presence of a modifier like `ftz`, compared against `true` or `false`
+    Flag(&'static str),
+    Strategy,
+    /// Tests that can run on the given `sm`
+    Sm,
+    /// Tests that compile with the given PTX ISA version
+    Ptx,
+}
+
+/// Single `key=value` condition of `--where`.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    key: Key,
+    value: String,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {s}"))?;
+        let key = match key {
+            "instr" => Key::Instr,
+            "type" => Key::Type,
+            "to" => Key::To,
+            "from" => Key::From,
+            "rnd" => Key::Rnd,
+            "mod" => Key::Mod,
+            "ftz" => Key::Flag("ftz"),
+            "sat" => Key::Flag("sat"),
+            "approx" => Key::Flag("approx"),
+            "strategy" => Key::Strategy,
+            "sm" => Key::Sm,
+            "ptx" => Key::Ptx,
+            _ => {
+                return Err(format!(
+                    "unknown key {key}, expected one of instr, type, to, from, rnd, mod, ftz, \
+                     sat, approx, strategy, sm, ptx"
+                ))
+            }
+        };
+        let valid = match key {
+            Key::Rnd => ROUNDINGS.contains(&value),
+            Key::Flag(_) => value == "true" || value == "false",
+            Key::Strategy => ["range", "random", "invalid"].contains(&value),
+            Key::Sm => value.parse::<u32>().is_ok(),
+            Key::Ptx => value.parse::<PtxVersion>().is_ok(),
+            Key::Instr | Key::Type | Key::To | Key::From | Key::Mod => !value.is_empty(),
+        };
+        if !valid {
+            return Err(format!("invalid value for {s}"));
+        }
+        Ok(Condition {
+            key,
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, test: &TestCase) -> bool {
+        let meta = &test.metadata;
+        let value = self.value.as_str();
+        match self.key {
+            Key::Instr => meta.instr == value,
+            Key::Type => meta.types.iter().any(|t| t == value),
+            Key::To => meta.types.first().is_some_and(|t| t == value),
+            Key::From => meta.types.last().is_some_and(|t| t == value),
+            Key::Rnd | Key::Mod => meta.modifiers.iter().any(|m| m == value),
+            Key::Flag(modifier) => {
+                meta.modifiers.iter().any(|m| m == modifier) == (value == "true")
+            }
+            Key::Strategy => test.test.strategy().as_str() == value,
+            Key::Sm => meta.min_sm <= value.parse().unwrap(),
+            Key::Ptx => meta.min_ptx <= value.parse().unwrap(),
+        }
+    }
+}
+
+/// Parse the comma separated conditions of a single `--where`.
+pub fn parse_conditions(conditions: String) -> Result<Vec<Condition>, String> {
+    conditions.split(',').map(str::parse).collect()
+}
+
+/// Whether `tag` names the test's instruction, one of its types or modifiers, or its strategy.
+pub fn has_tag(test: &TestCase, tag: &str) -> bool {
+    let meta = &test.metadata;
+    meta.instr == tag
+        || meta.types.iter().any(|t| t == tag)
+        || meta.modifiers.iter().any(|m| m == tag)
+        || test.test.strategy().as_str() == tag
+}
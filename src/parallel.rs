@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    isolate::{signal, tail},
+    report::TestRecord,
+    test::{TestCase, TestError},
+};
+
+/// How much of a dead worker's stderr is kept in the crash report.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// How many times a test is retried after the worker running it died.
+const MAX_RETRIES: usize = 1;
+
+/// Request sent by the supervisor to a worker, one JSON object per line on the worker's stdin.
+/// The worker answers every request with a `TestRecord` as one line of JSON on its stdout.
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkerRequest {
+    test: String,
+}
+
+/// Serve test requests read from stdin until the supervisor closes it, this is the worker side
+/// of `run_in_workers`. Nothing else may be printed to stdout while serving. A test the worker
+/// doesn't have is answered with a failed record, a request that can't be read ends serving
+/// with an error.
+pub fn serve(
+    tests: Vec<TestCase>,
+    mut execute: impl FnMut(TestCase) -> Result<(), TestError>,
+) -> Result<(), String> {
+    let mut tests: HashMap<_, _> = tests.into_iter().map(|t| (t.name.clone(), t)).collect();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|err| format!("Failed to read a test request: {err}"))?;
+        let request: WorkerRequest = serde_json::from_str(&line)
+            .map_err(|err| format!("Invalid test request `{line}`: {err}"))?;
+        let start = Instant::now();
+        let result = match tests.remove(&request.test) {
+            Some(t) => execute(t),
+            None => Err(TestError::UnknownTest {
+                name: request.test.clone(),
+            }),
+        };
+        let record = TestRecord::new(request.test, result, start.elapsed());
+        writeln!(stdout, "{}", serde_json::to_string(&record).unwrap()).unwrap();
+        stdout.flush().unwrap();
+    }
+    Ok(())
+}
+
+/// Run tests in child processes executing this binary, one child for every entry of `workers`,
+/// started with its arguments. Every worker takes the next test from a shared queue.
+///
+/// A worker that dies is restarted and the test it was running is put back into the queue, a
+/// test that keeps killing its workers is reported as crashed. A worker running a test for
+/// longer than the test's timeout is killed and the test is reported as timed out. `on_result`
+/// is called as soon as a test finishes and returns the record to keep, the returned results
+/// are in the order of `tests`.
+pub fn run_in_workers(
+    tests: Vec<(String, Option<Duration>)>,
+    workers: &[Vec<String>],
+    on_result: impl Fn(TestRecord) -> TestRecord + Sync,
+) -> Vec<TestRecord> {
+    let queue = Mutex::new(
+        (0..tests.len())
+            .map(|index| (index, 0))
+            .collect::<VecDeque<_>>(),
+    );
+    let results = Mutex::new(vec![None; tests.len()]);
+
+    thread::scope(|scope| {
+        for (id, args) in workers.iter().enumerate() {
+            let supervisor = Supervisor {
+                id,
+                args,
+                tests: &tests,
+                queue: &queue,
+                results: &results,
+                on_result: &on_result,
+            };
+            scope.spawn(move || supervisor.run());
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect()
+}
+
+struct Supervisor<'a, F> {
+    id: usize,
+    args: &'a [String],
+    /// Names of all tests with their timeouts
+    tests: &'a [(String, Option<Duration>)],
+    /// Indices of tests still to run, with the number of workers that died running them
+    queue: &'a Mutex<VecDeque<(usize, usize)>>,
+    results: &'a Mutex<Vec<Option<TestRecord>>>,
+    on_result: &'a F,
+}
+
+impl<F: Fn(TestRecord) -> TestRecord + Sync> Supervisor<'_, F> {
+    fn run(&self) {
+        loop {
+            let mut worker = Worker::spawn(self.id, self.args);
+            loop {
+                let Some((index, retries)) = self.queue.lock().unwrap().pop_front() else {
+                    worker.finish();
+                    return;
+                };
+                let (name, timeout) = &self.tests[index];
+                let start = Instant::now();
+                match worker.execute(name, *timeout) {
+                    Ok(record) => self.finish(index, record),
+                    Err(WorkerFailure::Timeout) => {
+                        worker.kill();
+                        let seconds = timeout.unwrap_or_default().as_secs_f64();
+                        let result = Err(TestError::Timeout { seconds });
+                        self.finish(
+                            index,
+                            TestRecord::new(name.clone(), result, start.elapsed()),
+                        );
+                        break;
+                    }
+                    Err(WorkerFailure::Died) => {
+                        let (signal, stderr_tail) = worker.kill();
+                        if retries < MAX_RETRIES {
+                            eprintln!("Worker {} died while running {name}, retrying", self.id);
+                            self.queue.lock().unwrap().push_back((index, retries + 1));
+                        } else {
+                            let result = Err(TestError::Crashed {
+                                signal,
+                                stderr_tail,
+                            });
+                            self.finish(
+                                index,
+                                TestRecord::new(name.clone(), result, start.elapsed()),
+                            );
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&self, index: usize, record: TestRecord) {
+        self.results.lock().unwrap()[index] = Some((self.on_result)(record));
+    }
+}
+
+enum WorkerFailure {
+    Timeout,
+    Died,
+}
+
+struct Worker {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    /// Lines of the worker's stdout, read on a separate thread so that waiting can time out
+    responses: Receiver<String>,
+    stderr: JoinHandle<Vec<u8>>,
+}
+
+impl Worker {
+    fn spawn(id: usize, args: &[String]) -> Self {
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        // Drain stderr concurrently, a child blocked on a full pipe would never exit. Every line,
+        // e.g. a warning, is forwarded to our stderr and kept for the crash report
+        let stderr_pipe = BufReader::new(child.stderr.take().unwrap());
+        let stderr = thread::spawn(move || {
+            let mut stderr = Vec::new();
+            for line in stderr_pipe.split(b'\n').map_while(Result::ok) {
+                eprintln!("Worker {id}: {}", String::from_utf8_lossy(&line));
+                stderr.extend(line);
+                stderr.push(b'\n');
+            }
+            stderr
+        });
+        Worker {
+            child,
+            stdin,
+            responses,
+            stderr,
+        }
+    }
+
+    fn execute(
+        &mut self,
+        test: &str,
+        timeout: Option<Duration>,
+    ) -> Result<TestRecord, WorkerFailure> {
+        let request = serde_json::to_string(&WorkerRequest {
+            test: test.to_string(),
+        })
+        .unwrap();
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{request}")
+            .and_then(|()| stdin.flush())
+            .map_err(|_| WorkerFailure::Died)?;
+        let response = match timeout {
+            Some(timeout) => self
+                .responses
+                .recv_timeout(timeout)
+                .map_err(|err| match err {
+                    RecvTimeoutError::Timeout => WorkerFailure::Timeout,
+                    RecvTimeoutError::Disconnected => WorkerFailure::Died,
+                })?,
+            None => self.responses.recv().map_err(|_| WorkerFailure::Died)?,
+        };
+        // Anything that is not a record means the worker is in an unknown state
+        serde_json::from_str(&response).map_err(|_| WorkerFailure::Died)
+    }
+
+    /// Close stdin, which tells the worker to exit once it finished the last test.
+    fn finish(mut self) {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+
+    /// Kill the worker, returns the signal that terminated it and the tail of its stderr.
+    fn kill(mut self) -> (Option<i32>, String) {
+        drop(self.stdin.take());
+        let _ = self.child.kill();
+        let status = self.child.wait().unwrap();
+        let stderr = self.stderr.join().unwrap();
+        (signal(status), tail(&stderr, STDERR_TAIL_LINES))
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::test::TestError;
+
+/// Information about the environment a run was executed in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunHeader {
+    pub cuda: String,
+    pub nvrtc: Option<String>,
+    pub arguments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Ok,
+    Fail,
+    /// Failed, but listed as a known failure with `--expectations`
+    Xfail,
+    /// Passed, even though it's listed as a known failure
+    Xpass,
+    /// Not executed, as requested with `--expectations`
+    Skip,
+}
+
+/// Result of a single executed test case.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestRecord {
+    pub name: String,
+    pub status: TestStatus,
+    pub duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<TestError>,
+    /// Reason given for expected failures and skipped tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl TestRecord {
+    pub fn new(name: String, result: Result<(), TestError>, duration: Duration) -> Self {
+        let (status, error) = match result {
+            Ok(()) => (TestStatus::Ok, None),
+            Err(err) => (TestStatus::Fail, Some(err)),
+        };
+        TestRecord {
+            name,
+            status,
+            duration_secs: duration.as_secs_f64(),
+            error,
+            reason: None,
+        }
+    }
+
+    /// Unexpected passes count as failures, so that stale expectations get noticed.
+    pub fn failed(&self) -> bool {
+        matches!(self.status, TestStatus::Fail | TestStatus::Xpass)
+    }
+
+    /// Whether the test itself passed, regardless of what was expected of it.
+    pub fn passed(&self) -> bool {
+        matches!(self.status, TestStatus::Ok | TestStatus::Xpass)
+    }
+
+    /// Share of passing cases, only known for tests that passed or found mismatching values.
+    pub fn pass_rate(&self) -> Option<f64> {
+        match &self.error {
+            _ if self.passed() => Some(1.0),
+            Some(TestError::ResultMismatch(mismatch)) => {
+                Some(mismatch.passed_cases as f64 / mismatch.total_cases as f64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Short description of the result, e.g. `99.1234% of cases passed`.
+    pub fn outcome(&self) -> String {
+        match (&self.error, self.pass_rate()) {
+            _ if self.status == TestStatus::Skip => "skipped".to_string(),
+            _ if self.passed() => "OK".to_string(),
+            (_, Some(rate)) => format!("{} of cases passed", format_rate(rate)),
+            (Some(err), None) => junit_kind(err).1.to_string(),
+            (None, None) => "failed".to_string(),
+        }
+    }
+}
+
+/// Machine-readable results of a whole run, as written by `--report`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub header: RunHeader,
+    pub tests: Vec<TestRecord>,
+}
+
+impl Report {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Write the results as JUnit XML, with one `<testsuite>` per instruction.
+    pub fn write_junit(&self, path: &Path) -> io::Result<()> {
+        let mut suites = BTreeMap::<&str, Vec<&TestRecord>>::new();
+        for test in self.tests.iter() {
+            suites.entry(suite_name(&test.name)).or_default().push(test);
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            file,
+            r#"<testsuites name="ptx_tests" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+            self.tests.len(),
+            count_kind(self.tests.iter(), "failure"),
+            count_kind(self.tests.iter(), "error"),
+            count_kind(self.tests.iter(), "skipped"),
+            total_time(self.tests.iter()),
+        )?;
+        for (suite, tests) in suites {
+            writeln!(
+                file,
+                r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+                xml_escape(suite),
+                tests.len(),
+                count_kind(tests.iter().copied(), "failure"),
+                count_kind(tests.iter().copied(), "error"),
+                count_kind(tests.iter().copied(), "skipped"),
+                total_time(tests.iter().copied()),
+            )?;
+            for test in tests {
+                write!(
+                    file,
+                    r#"    <testcase name="{}" classname="{}" time="{:.3}""#,
+                    xml_escape(&test.name),
+                    xml_escape(suite),
+                    test.duration_secs,
+                )?;
+                match junit_result(test) {
+                    None => writeln!(file, "/>")?,
+                    Some((tag, message, text)) => {
+                        writeln!(file, ">")?;
+                        writeln!(
+                            file,
+                            r#"      <{tag} message="{message}">{}</{tag}>"#,
+                            xml_escape(&text)
+                        )?;
+                        writeln!(file, "    </testcase>")?;
+                    }
+                }
+            }
+            writeln!(file, "  </testsuite>")?;
+        }
+        writeln!(file, "</testsuites>")?;
+        file.flush()
+    }
+}
+
+/// Tests that changed between two runs, in the order of the newer run.
+#[derive(Debug, Default)]
+pub struct Comparison<'a> {
+    /// Tests that passed in the base run and fail in the new one, as `[base, new]`
+    pub newly_failing: Vec<[&'a TestRecord; 2]>,
+    pub newly_passing: Vec<[&'a TestRecord; 2]>,
+    /// Tests failing in both runs with a different share of passing cases
+    pub pass_rate_changed: Vec<[&'a TestRecord; 2]>,
+    /// Tests executed only in the base run
+    pub removed: Vec<&'a TestRecord>,
+    /// Tests executed only in the new run
+    pub added: Vec<&'a TestRecord>,
+}
+
+impl<'a> Comparison<'a> {
+    /// Compare two runs, skipped tests are ignored.
+    pub fn new(base: &'a Report, new: &'a Report) -> Self {
+        let executed = |report: &'a Report| {
+            report
+                .tests
+                .iter()
+                .filter(|t| t.status != TestStatus::Skip)
+                .map(|t| (t.name.as_str(), t))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let base_tests = executed(base);
+        let new_tests = executed(new);
+
+        let mut comparison = Comparison::default();
+        for new_test in new.tests.iter().filter(|t| t.status != TestStatus::Skip) {
+            let Some(&base_test) = base_tests.get(new_test.name.as_str()) else {
+                comparison.added.push(new_test);
+                continue;
+            };
+            let pair = [base_test, new_test];
+            match (base_test.passed(), new_test.passed()) {
+                (true, false) => comparison.newly_failing.push(pair),
+                (false, true) => comparison.newly_passing.push(pair),
+                (false, false) if base_test.pass_rate() != new_test.pass_rate() => {
+                    comparison.pass_rate_changed.push(pair)
+                }
+                _ => {}
+            }
+        }
+        comparison.removed = base
+            .tests
+            .iter()
+            .filter(|t| t.status != TestStatus::Skip && !new_tests.contains_key(t.name.as_str()))
+            .collect();
+        comparison
+    }
+
+    /// Number of tests that got worse, either failing or passing fewer cases.
+    pub fn regressions(&self) -> usize {
+        let worse = |[base, new]: &[&TestRecord; 2]| {
+            new.pass_rate().unwrap_or(0.0) < base.pass_rate().unwrap_or(0.0)
+        };
+        self.newly_failing.len()
+            + self
+                .pass_rate_changed
+                .iter()
+                .filter(|pair| worse(pair))
+                .count()
+    }
+}
+
+/// Format a share of passing cases as a percentage, never rounding a failure up to 100%.
+pub fn format_rate(rate: f64) -> String {
+    let percent = rate * 100.0;
+    if rate < 1.0 && format!("{percent:.4}") == "100.0000" {
+        "99.9999%".to_string()
+    } else {
+        format!("{percent:.4}%")
+    }
+}
+
+/// Tests are named after the instruction they exercise, e.g. `cvt_rzi_ftz_sat_s16_f32`.
+fn suite_name(test: &str) -> &str {
+    test.split('_').next().unwrap_or(test)
+}
+
+/// JUnit distinguishes between failed assertions (`<failure>`) and tests that could not run
+/// (`<error>`).
+fn junit_kind(err: &TestError) -> (&'static str, &'static str) {
+    match err {
+        TestError::ResultMismatch(_) => ("failure", "Result mismatch"),
+        TestError::CompilationFail { .. } => ("error", "Compilation failed"),
+        TestError::CompilationSuccess { .. } => ("error", "Unexpected compilation success"),
+        TestError::MissingRunFunction => ("error", "Missing run function"),
+        TestError::Crashed { .. } => ("error", "Crashed"),
+        TestError::Timeout { .. } => ("error", "Timed out"),
+        TestError::DriverError { .. } => ("error", "Driver error"),
+        TestError::Golden { .. } => ("error", "Golden outputs unavailable"),
+        TestError::UnknownTest { .. } => ("error", "Unknown test"),
+    }
+}
+
+/// JUnit element describing the result of a test that did not pass, with its message and text.
+/// Expected failures are reported as skipped, they are not something to act on.
+fn junit_result(test: &TestRecord) -> Option<(&'static str, &'static str, String)> {
+    let reason = test.reason.as_deref().unwrap_or_default();
+    let error = test
+        .error
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    match test.status {
+        TestStatus::Ok => None,
+        TestStatus::Fail => {
+            let (tag, message) = test.error.as_ref().map_or(("error", "Failed"), junit_kind);
+            Some((tag, message, error))
+        }
+        TestStatus::Xfail => Some(("skipped", "Expected failure", format!("{reason}\n{error}"))),
+        TestStatus::Xpass => Some(("failure", "Unexpected pass", reason.to_string())),
+        TestStatus::Skip => Some(("skipped", "Skipped", reason.to_string())),
+    }
+}
+
+fn count_kind<'a>(tests: impl Iterator<Item = &'a TestRecord>, kind: &str) -> usize {
+    tests
+        .filter(|t| matches!(junit_result(t), Some((tag, _, _)) if tag == kind))
+        .count()
+}
+
+fn total_time<'a>(tests: impl Iterator<Item = &'a TestRecord>) -> f64 {
+    tests.map(|t| t.duration_secs).sum()
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,490 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::metadata::{PtxVersion, TestMetadata};
+use crate::test::{
+    make_random, make_range, DebugRich, OnDevice, RandomTest, RangeTest, TestCase, TestCommon,
+    TestFunction, TestPtx, GROUP_SIZE,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpecFile {
+    #[serde(default)]
+    test: Vec<Spec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Spec {
+    name: String,
+    /// Names of the kernel parameters, one for every input followed by the output
+    args: Vec<String>,
+    /// Type of every input, e.g. `f32`
+    inputs: Vec<String>,
+    output: String,
+    /// PTX placed after the parameters are loaded, like the `.ptx` templates of built-in tests
+    body: String,
+    strategy: Option<SpecStrategy>,
+    /// Name of a built-in test whose host reference verifies the outputs
+    reference: Option<String>,
+    #[serde(default)]
+    expected: Vec<Expected>,
+    /// Lowest `sm` the test can run on, see `TestMetadata::min_sm`
+    #[serde(default)]
+    min_sm: u32,
+    /// Lowest PTX version the test compiles with, e.g. `"7.8"`
+    min_ptx: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum SpecStrategy {
+    /// All values of the lowest `bits` bits of the inputs, spread over the inputs starting from
+    /// the lowest bits of the first one
+    Range { bits: u32 },
+    /// Random inputs, every input is a special value of its type, like NaN or the maximum, with
+    /// probability `special` and random bits otherwise
+    Random {
+        #[serde(default)]
+        special: f64,
+    },
+}
+
+/// Explicit case of a test without a reference, values are hex bit patterns.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expected {
+    input: Vec<String>,
+    output: String,
+}
+
+/// Load tests from TOML files given with `--spec`, `path` is either a file or a directory
+/// of `.toml` files:
+///
+/// ```toml
+/// [[test]]
+/// name = "spec_add_rz_f32"
+/// args = ["input_a", "input_b", "output"]
+/// inputs = ["f32", "f32"]
+/// output = "f32"
+/// strategy = { kind = "random", special = 0.25 }
+/// reference = "add_rz_f32"
+/// body = """
+/// ...
+/// add.rz.f32 sum, a, b;
+/// ...
+/// """
+/// ```
+///
+/// `min_sm = 89` and `min_ptx = "7.8"` skip the test on older targets, see `--target`.
+/// `strategy` is either `{ kind = "range", bits = N }` or `{ kind = "random", special = P }`.
+/// Instead of `strategy` and `reference` a test can list its cases with expected outputs as
+/// `[[test.expected]]` tables with `input = ["0x3f800000", "0x0"]` and `output = "0x3f800000"`.
+///
+/// `references` are the built-in tests a spec can name as its `reference`.
+pub fn load(
+    path: &Path,
+    references: &HashMap<String, TestFunction>,
+) -> Result<Vec<TestCase>, String> {
+    let files = if path.is_dir() {
+        let mut files = fs::read_dir(path)
+            .map_err(|err| format!("{}: {err}", path.display()))?
+            .map(|entry| entry.unwrap().path())
+            .filter(|file| file.extension().is_some_and(|ext| ext == "toml"))
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut tests = Vec::new();
+    for file in files {
+        let text = fs::read_to_string(&file).map_err(|err| format!("{}: {err}", file.display()))?;
+        let specs: SpecFile =
+            toml::from_str(&text).map_err(|err| format!("{}: {err}", file.display()))?;
+        for spec in specs.test {
+            let name = spec.name.clone();
+            let min_ptx = match &spec.min_ptx {
+                Some(version) => version.parse::<PtxVersion>(),
+                None => Ok(PtxVersion::default()),
+            };
+            let metadata = TestMetadata {
+                min_sm: spec.min_sm,
+                min_ptx: min_ptx.map_err(|err| format!("{}: {name}: {err}", file.display()))?,
+                ..Default::default()
+            };
+            let test = build(spec, references)
+                .map_err(|err| format!("{}: {name}: {err}", file.display()))?;
+            tests.push(TestCase::new(name, test).with_metadata(metadata));
+        }
+    }
+    Ok(tests)
+}
+
+fn type_size(name: &str) -> Result<usize, String> {
+    match name {
+        "b16" | "u16" | "s16" | "f16" | "bf16" => Ok(2),
+        "b32" | "u32" | "s32" | "f32" => Ok(4),
+        "b64" | "u64" | "s64" | "f64" => Ok(8),
+        _ => Err(format!("unsupported type {name}")),
+    }
+}
+
+/// Values at the edges of a type: zeros, ones, extremes, infinities and NaN.
+fn special_values(name: &str) -> Vec<u64> {
+    let bits = type_size(name).unwrap() as u32 * 8;
+    let sign = 1 << (bits - 1);
+    let (exponent, mantissa) = match name {
+        "f16" => (5, 10),
+        "bf16" => (8, 7),
+        "f32" => (8, 23),
+        "f64" => (11, 52),
+        _ => {
+            let max = u64::MAX >> (64 - bits);
+            return vec![0, 1, max, sign, sign - 1];
+        }
+    };
+    let mantissa_mask = (1 << mantissa) - 1;
+    let exponent_max: u64 = (1 << exponent) - 1;
+    let infinity = exponent_max << mantissa;
+    let positive = [
+        0,
+        1,
+        mantissa_mask,
+        1 << mantissa,
+        (exponent_max >> 1) << mantissa,
+        infinity - 1,
+        infinity,
+        infinity | (1 << (mantissa - 1)),
+    ];
+    positive
+        .iter()
+        .flat_map(|&value| [value, value | sign])
+        .collect()
+}
+
+fn parse_bits(value: &str, size: usize) -> Result<u64, String> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    let bits = u64::from_str_radix(digits, 16).map_err(|_| format!("invalid hex value {value}"))?;
+    if size < 8 && bits >> (size * 8) != 0 {
+        return Err(format!("value {value} does not fit in {size} bytes"));
+    }
+    Ok(bits)
+}
+
+enum Reference {
+    Test(TestFunction),
+    Table(HashMap<Vec<u64>, u64>),
+}
+
+enum Inputs {
+    Range { bits: u32 },
+    Random { special: f64 },
+    Table(Vec<Vec<u64>>),
+}
+
+struct Built {
+    args: Vec<String>,
+    body: String,
+    input_sizes: Vec<usize>,
+    specials: Vec<Vec<u64>>,
+    inputs: Inputs,
+    reference: Reference,
+}
+
+fn build(spec: Spec, references: &HashMap<String, TestFunction>) -> Result<TestFunction, String> {
+    if !(1..=3).contains(&spec.inputs.len()) {
+        return Err("expected one to three inputs".to_string());
+    }
+    if spec.args.len() != spec.inputs.len() + 1 {
+        return Err(format!(
+            "expected {} args, one for every input and one for the output",
+            spec.inputs.len() + 1
+        ));
+    }
+    let input_sizes = spec
+        .inputs
+        .iter()
+        .map(|name| type_size(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output_size = type_size(&spec.output)?;
+    let (inputs, reference) = match (spec.strategy, spec.reference) {
+        (Some(strategy), Some(reference)) => {
+            if !spec.expected.is_empty() {
+                return Err("expected outputs can't be combined with a reference".to_string());
+            }
+            let reference = references
+                .get(&reference)
+                .ok_or_else(|| format!("unknown reference test {reference}"))?;
+            if reference.layout() != Some((input_sizes.clone(), output_size)) {
+                return Err("types differ in size from the reference test".to_string());
+            }
+            let inputs = match strategy {
+                SpecStrategy::Range { bits } => {
+                    let total_bits = input_sizes.iter().sum::<usize>() * 8;
+                    if bits > 32 || 1 << bits < GROUP_SIZE || bits as usize > total_bits {
+                        return Err(format!(
+                            "range must be at least {} and at most 32 bits and fit in the inputs",
+                            GROUP_SIZE.ilog2()
+                        ));
+                    }
+                    Inputs::Range { bits }
+                }
+                SpecStrategy::Random { special } => {
+                    if !(0.0..=1.0).contains(&special) {
+                        return Err("special must be between 0 and 1".to_string());
+                    }
+                    Inputs::Random { special }
+                }
+            };
+            (inputs, Reference::Test(reference.clone()))
+        }
+        (None, None) => {
+            if spec.expected.is_empty() {
+                return Err(
+                    "expected either a strategy and a reference or expected outputs".to_string(),
+                );
+            }
+            let mut cases = Vec::new();
+            let mut table = HashMap::new();
+            for expected in spec.expected {
+                if expected.input.len() != input_sizes.len() {
+                    return Err(format!(
+                        "expected {} values in every input",
+                        input_sizes.len()
+                    ));
+                }
+                let input = expected
+                    .input
+                    .iter()
+                    .zip(input_sizes.iter())
+                    .map(|(value, &size)| parse_bits(value, size))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let output = parse_bits(&expected.output, output_size)?;
+                match table.insert(input.clone(), output) {
+                    Some(previous) if previous != output => {
+                        return Err(format!(
+                            "input {} is listed with different outputs",
+                            expected.input.join(", ")
+                        ));
+                    }
+                    Some(_) => {}
+                    None => cases.push(input),
+                }
+            }
+            (Inputs::Table(cases), Reference::Table(table))
+        }
+        _ => return Err("strategy and reference must be given together".to_string()),
+    };
+    let built = Built {
+        args: spec.args,
+        body: spec.body,
+        specials: spec
+            .inputs
+            .iter()
+            .map(|name| special_values(name))
+            .collect(),
+        input_sizes,
+        inputs,
+        reference,
+    };
+
+    // Inputs and outputs are carried in unsigned integers of the same size as their types
+    macro_rules! by_size {
+        ($size:expr, $t:ident, $body:expr) => {
+            match $size {
+                2 => {
+                    type $t = u16;
+                    $body
+                }
+                4 => {
+                    type $t = u32;
+                    $body
+                }
+                _ => {
+                    type $t = u64;
+                    $body
+                }
+            }
+        };
+    }
+    let sizes = built.input_sizes.clone();
+    Ok(match sizes[..] {
+        [a] => by_size!(a, A, by_size!(output_size, O, finish::<A, O>(built))),
+        [a, b] => by_size!(
+            a,
+            A,
+            by_size!(b, B, by_size!(output_size, O, finish::<(A, B), O>(built)))
+        ),
+        _ => by_size!(
+            sizes[0],
+            A,
+            by_size!(
+                sizes[1],
+                B,
+                by_size!(
+                    sizes[2],
+                    C,
+                    by_size!(output_size, O, finish::<(A, B, C), O>(built))
+                )
+            )
+        ),
+    })
+}
+
+fn finish<I: Bits, O: Bits>(built: Built) -> TestFunction {
+    let test = SpecTest::<I, O> {
+        built,
+        _phantom: Default::default(),
+    };
+    match test.built.inputs {
+        Inputs::Random { .. } => make_random(test),
+        Inputs::Range { .. } | Inputs::Table(_) => make_range(test),
+    }
+}
+
+/// Conversion of test values to and from raw bits, one value for every component.
+trait Bits: OnDevice + DebugRich + 'static {
+    fn to_bits(self) -> Vec<u64>;
+    fn from_bits(bits: &[u64]) -> Self;
+}
+
+macro_rules! impl_bits {
+    ($type:ty) => {
+        impl Bits for $type {
+            fn to_bits(self) -> Vec<u64> {
+                vec![self as u64]
+            }
+
+            fn from_bits(bits: &[u64]) -> Self {
+                bits[0] as $type
+            }
+        }
+    };
+}
+
+impl_bits!(u16);
+impl_bits!(u32);
+impl_bits!(u64);
+
+impl<A: Bits, B: Bits> Bits for (A, B) {
+    fn to_bits(self) -> Vec<u64> {
+        [self.0.to_bits(), self.1.to_bits()].concat()
+    }
+
+    fn from_bits(bits: &[u64]) -> Self {
+        (A::from_bits(&bits[0..]), B::from_bits(&bits[1..]))
+    }
+}
+
+impl<A: Bits, B: Bits, C: Bits> Bits for (A, B, C) {
+    fn to_bits(self) -> Vec<u64> {
+        [self.0.to_bits(), self.1.to_bits(), self.2.to_bits()].concat()
+    }
+
+    fn from_bits(bits: &[u64]) -> Self {
+        (
+            A::from_bits(&bits[0..]),
+            B::from_bits(&bits[1..]),
+            C::from_bits(&bits[2..]),
+        )
+    }
+}
+
+struct SpecTest<I, O> {
+    built: Built,
+    _phantom: std::marker::PhantomData<fn() -> (I, O)>,
+}
+
+impl<I, O> TestPtx for SpecTest<I, O> {
+    fn body(&self) -> String {
+        self.built.body.clone()
+    }
+
+    fn args(&self) -> Vec<&str> {
+        self.built.args.iter().map(String::as_str).collect()
+    }
+}
+
+impl<I: Bits, O: Bits> TestCommon for SpecTest<I, O> {
+    type Input = I;
+    type Output = O;
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let input = input.to_bits();
+        let output = output.to_bits()[0];
+        let expected = match &self.built.reference {
+            Reference::Test(test) => {
+                return test
+                    .host_verify_bits(&input, output)
+                    .map_err(|e| O::from_bits(&[e]))
+            }
+            Reference::Table(table) => table[&input],
+        };
+        if output == expected {
+            Ok(())
+        } else {
+            Err(O::from_bits(&[expected]))
+        }
+    }
+}
+
+impl<I: Bits, O: Bits> RangeTest for SpecTest<I, O> {
+    fn generate(&self, input: u32) -> Self::Input {
+        match &self.built.inputs {
+            Inputs::Range { bits } => {
+                let mut rest = input as u64 & (u64::MAX >> (64 - bits));
+                let components: Vec<_> = self
+                    .built
+                    .input_sizes
+                    .iter()
+                    .map(|size| {
+                        let bits = size * 8;
+                        let value = rest & (u64::MAX >> (64 - bits));
+                        rest = rest.checked_shr(bits as u32).unwrap_or(0);
+                        value
+                    })
+                    .collect();
+                I::from_bits(&components)
+            }
+            Inputs::Table(cases) => I::from_bits(&cases[input as usize]),
+            Inputs::Random { .. } => unreachable!(),
+        }
+    }
+
+    fn max_value(&self) -> u32 {
+        match &self.built.inputs {
+            Inputs::Range { bits } => (u64::MAX >> (64 - bits)) as u32,
+            Inputs::Table(cases) => (cases.len() - 1) as u32,
+            Inputs::Random { .. } => unreachable!(),
+        }
+    }
+}
+
+impl<I: Bits, O: Bits> RandomTest for SpecTest<I, O> {
+    fn generate<R: Rng>(&self, rng: &mut R) -> Self::Input {
+        let Inputs::Random { special } = self.built.inputs else {
+            unreachable!()
+        };
+        let components: Vec<_> = self
+            .built
+            .input_sizes
+            .iter()
+            .zip(self.built.specials.iter())
+            .map(|(size, specials)| {
+                if rng.gen_bool(special) {
+                    specials[rng.gen_range(0..specials.len())]
+                } else {
+                    rng.gen::<u64>() & (u64::MAX >> (64 - size * 8))
+                }
+            })
+            .collect();
+        I::from_bits(&components)
+    }
+}
@@ -1,42 +1,108 @@
 use float8::{F8E4M3, F8E5M2};
-use num::{Bounded, Num, PrimInt, Zero};
+use num::{Bounded, Num, Zero};
 use rand::{Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
-use std::{any::Any, fmt::Debug, mem, ptr, u32};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    io, mem,
+    ops::Range,
+    path::Path,
+    ptr, slice,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+    u32,
+};
 
 use crate::{
-    cuda::{CUmodule, Cuda},
+    cuda::{CUfunction, CUmodule, CUstream},
+    driver::{Driver, DriverFailure},
+    golden::{self, GoldenKey, GoldenReader, GoldenWriter},
+    metadata::{Strategy, TestMetadata},
     TestContext,
 };
 
+/// Call a `Driver` method, turning a failure into `TestError::DriverError`.
+macro_rules! driver {
+    ($driver:expr, $method:ident($($arg:expr),* $(,)?)) => {
+        $driver
+            .$method($($arg),*)
+            .map_err(|failure| driver_error(stringify!($method), failure))
+    };
+}
+
+fn driver_error(call: &str, failure: DriverFailure) -> TestError {
+    TestError::DriverError {
+        call: call.to_string(),
+        code: failure.code,
+        name: failure.name,
+        description: failure.description,
+    }
+}
+
+/// Resources are released in `Drop`, where errors can't be returned to the test.
+fn warn_on_error(result: Result<(), TestError>) {
+    if let Err(err) = result {
+        eprintln!("Warning: {err}");
+    }
+}
+
 struct CudaModule<'a> {
-    cuda: &'a Cuda,
+    driver: &'a dyn Driver,
     value: CUmodule,
 }
 
 impl<'a> Drop for CudaModule<'a> {
     fn drop(&mut self) {
-        unsafe { self.cuda.cuModuleUnload(self.value) }.unwrap();
+        warn_on_error(driver!(self.driver, unload_module(self.value)));
     }
 }
 
 struct DevicePtr<'a> {
-    cuda: &'a Cuda,
+    driver: &'a dyn Driver,
     value: u64,
 }
 
 impl<'a> Drop for DevicePtr<'a> {
     fn drop(&mut self) {
-        unsafe { self.cuda.cuMemFree_v2(self.value) }.unwrap();
+        warn_on_error(driver!(self.driver, mem_free(self.value)));
+    }
+}
+
+struct HostPtr<'a> {
+    driver: &'a dyn Driver,
+    value: *mut u8,
+}
+
+impl<'a> Drop for HostPtr<'a> {
+    fn drop(&mut self) {
+        warn_on_error(unsafe { driver!(self.driver, mem_free_host(self.value)) });
+    }
+}
+
+struct CudaStream<'a> {
+    driver: &'a dyn Driver,
+    value: CUstream,
+}
+
+impl<'a> Drop for CudaStream<'a> {
+    fn drop(&mut self) {
+        warn_on_error(driver!(self.driver, stream_destroy(self.value)));
     }
 }
 
 pub trait TestPtx {
-    fn args(&self) -> &[&str];
+    fn args(&self) -> Vec<&str>;
     fn body(&self) -> String;
 }
 
-pub trait TestCommon: TestPtx {
+/// Tests are shared between the threads verifying their results.
+pub trait TestCommon: TestPtx + Sync {
     type Input: OnDevice + DebugRich;
     type Output: OnDevice + DebugRich;
 
@@ -46,13 +112,19 @@ pub trait TestCommon: TestPtx {
 pub trait RangeTest: TestCommon {
     const MAX_VALUE: u32 = u32::MAX;
     fn generate(&self, input: u32) -> Self::Input;
+    /// Inputs `0..=max_value()` are generated, only tests built at runtime need to override it
+    fn max_value(&self) -> u32 {
+        Self::MAX_VALUE
+    }
 }
 
 pub trait RandomTest: TestCommon {
+    /// Number of generated cases, unless overridden with `--random-cases`
+    const CASES: usize = 1 << 32;
     fn generate<R: Rng>(&self, rng: &mut R) -> Self::Input;
 }
 
-pub trait OnDevice: Copy + Debug {
+pub trait OnDevice: Copy + Debug + Send + Sync {
     const COMPONENTS: usize;
     fn write(self, buffers: &mut [Vec<u8>]);
     fn read(buffers: &[Vec<u8>], index: usize) -> Self;
@@ -652,334 +724,1434 @@ impl Fp8 for F8E5M2 {
     }
 }
 
-const SEED: u64 = 0x761194f3027874ef;
-const GROUP_SIZE: usize = 128;
+pub const DEFAULT_SEED: u64 = 0x761194f3027874ef;
+pub const GROUP_SIZE: usize = 128;
+/// Number of batches in flight at the same time
+const PIPELINE_DEPTH: usize = 2;
+const VERIFY_CHUNK_SIZE: usize = 1 << 16;
+/// Number of cases verified at once when replaying recorded outputs
+const REPLAY_BATCH_SIZE: usize = 1 << 22;
 // Totally unscientific number that works on my machine
 const SAFE_MEMORY_LIMIT: usize = 1 << 29;
 
 fn load_module<'a>(ctx: &'a dyn TestContext, t: &dyn TestPtx) -> Result<CudaModule<'a>, TestError> {
-    let cuda = ctx.cuda();
+    let driver = ctx.driver();
 
     match ctx.prepare_test_source(t) {
-        Ok(src) => {
-            let mut module = ptr::null_mut();
-            let load_result = unsafe { cuda.cuModuleLoadData(&mut module, src.as_ptr() as _) };
-
-            match load_result {
-                Ok(()) => Ok(CudaModule {
-                    cuda,
-                    value: module,
-                }),
-                Err(code) => {
-                    return Err(TestError::CompilationFail {
-                        message: format!("CUDA Error {code}"),
-                    })
-                }
+        Ok(src) => match driver.load_module(&src) {
+            Ok(module) => Ok(CudaModule {
+                driver,
+                value: module,
+            }),
+            Err(failure) => {
+                return Err(TestError::CompilationFail {
+                    message: driver_error("load_module", failure).to_string(),
+                })
             }
-        }
+        },
         Err(message) => return Err(TestError::CompilationFail { message }),
     }
 }
 
 pub fn run_random<Test: RandomTest>(
     ctx: &dyn TestContext,
-    t: Test,
-    fail_fast: bool,
+    t: &Test,
+    opts: &TestOptions,
+) -> Result<(), TestError> {
+    let mut rng = XorShiftRng::seed_from_u64(opts.seed);
+    let total_elements = opts.random_cases.unwrap_or(Test::CASES);
+    assert!(total_elements.is_multiple_of(GROUP_SIZE));
+    let result = run_batches(
+        ctx,
+        t,
+        opts,
+        Some(opts.seed),
+        total_elements,
+        |_, inputs| t.generate(&mut rng).write(inputs),
+    );
+    with_seed(result, opts.seed)
+}
+
+fn with_seed(result: Result<(), TestError>, seed: u64) -> Result<(), TestError> {
+    result.map_err(|mut err| {
+        if let TestError::ResultMismatch(mismatch) = &mut err {
+            mismatch.seed = Some(seed);
+        }
+        err
+    })
+}
+
+pub fn run_range<Test: RangeTest>(
+    ctx: &dyn TestContext,
+    t: &Test,
+    opts: &TestOptions,
+) -> Result<(), TestError> {
+    let cases = t.max_value() as usize + 1;
+    run_batches(ctx, t, opts, None, cases, |index, inputs| {
+        t.generate(index as u32).write(inputs)
+    })
+}
+
+pub fn replay_random<Test: RandomTest>(
+    t: &Test,
+    path: &Path,
+    opts: &TestOptions,
+    source: &str,
+) -> Result<(), TestError> {
+    let mut rng = XorShiftRng::seed_from_u64(opts.seed);
+    let total_elements = opts.random_cases.unwrap_or(Test::CASES);
+    let result = run_replay(
+        t,
+        path,
+        opts,
+        source,
+        Some(opts.seed),
+        total_elements,
+        |_, inputs| t.generate(&mut rng).write(inputs),
+    );
+    with_seed(result, opts.seed)
+}
+
+pub fn replay_range<Test: RangeTest>(
+    t: &Test,
+    path: &Path,
+    opts: &TestOptions,
+    source: &str,
+) -> Result<(), TestError> {
+    let cases = t.max_value() as usize + 1;
+    run_replay(t, path, opts, source, None, cases, |index, inputs| {
+        t.generate(index as u32).write(inputs)
+    })
+}
+
+/// Verify outputs recorded with `--record` on the host, without launching anything on a GPU.
+/// `source` is the golden source of the test, see `TestContext::golden_source`.
+fn run_replay<Test: TestCommon>(
+    t: &Test,
+    path: &Path,
+    opts: &TestOptions,
+    source: &str,
+    seed: Option<u64>,
+    cases: usize,
+    mut generate: impl FnMut(usize, &mut [Vec<u8>]),
 ) -> Result<(), TestError> {
-    let cuda = ctx.cuda();
+    assert_eq!(Test::Output::COMPONENTS, 1);
+    let total_elements = cases.next_multiple_of(GROUP_SIZE);
+    let mut generate =
+        |index: usize, inputs: &mut [Vec<u8>]| generate(index.min(cases - 1), inputs);
+    let output_size = Test::Output::size_of();
+    let key = GoldenKey::new(source, seed, total_elements, output_size);
+    let mut reader =
+        GoldenReader::open(path, &key).map_err(|message| TestError::Golden { message })?;
+    let mut verification = Verification::new(opts, cases);
+    let mut inputs = vec![Vec::new(); Test::Input::COMPONENTS];
 
-    let module = load_module(ctx, &t)?;
-    let mut kernel = ptr::null_mut();
-    unsafe { cuda.cuModuleGetFunction(&mut kernel, module.value, c"run".as_ptr()) }
+    for element_start in (0..total_elements).step_by(REPLAY_BATCH_SIZE) {
+        let len = REPLAY_BATCH_SIZE.min(total_elements - element_start);
+        for vec in inputs.iter_mut() {
+            vec.clear();
+        }
+        for index in element_start..element_start + len {
+            generate(index, &mut inputs);
+        }
+        let outputs = [reader.read(len * output_size).map_err(golden_error(path))?];
+        verification.verify_batch(t, &inputs, &outputs, None, element_start);
+    }
+
+    verification.into_result()
+}
+
+fn golden_error(path: &Path) -> impl Fn(io::Error) -> TestError + '_ {
+    move |err| TestError::Golden {
+        message: format!("{}: {err}", path.display()),
+    }
+}
+
+/// Golden file of the running test, opened according to `TestOptions::golden`.
+enum GoldenFile<'a> {
+    None,
+    Record(&'a Path, GoldenWriter),
+    Compare(&'a Path, GoldenReader),
+}
+
+impl<'a> GoldenFile<'a> {
+    fn open(golden: Option<&'a golden::Golden>, key: &GoldenKey) -> Result<Self, TestError> {
+        Ok(match golden {
+            None => GoldenFile::None,
+            Some(golden::Golden::Record(path)) => GoldenFile::Record(
+                path,
+                GoldenWriter::create(path, key).map_err(golden_error(path))?,
+            ),
+            Some(golden::Golden::Compare(path)) => GoldenFile::Compare(
+                path,
+                GoldenReader::open(path, key).map_err(|message| TestError::Golden { message })?,
+            ),
+        })
+    }
+
+    /// Record the outputs of the next batch, or read the golden outputs to compare them against.
+    fn next_batch(&mut self, outputs: &[Vec<u8>]) -> Result<Option<[Vec<u8>; 1]>, TestError> {
+        match self {
+            GoldenFile::None => Ok(None),
+            GoldenFile::Record(path, writer) => {
+                writer.write(&outputs[0]).map_err(golden_error(path))?;
+                Ok(None)
+            }
+            GoldenFile::Compare(path, reader) => {
+                let expected = reader.read(outputs[0].len()).map_err(golden_error(path))?;
+                Ok(Some([expected]))
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), TestError> {
+        match self {
+            GoldenFile::Record(path, writer) => writer.finish().map_err(golden_error(path)),
+            GoldenFile::None | GoldenFile::Compare(..) => Ok(()),
+        }
+    }
+}
+
+fn load_kernel<'a>(
+    ctx: &'a dyn TestContext,
+    t: &dyn TestPtx,
+) -> Result<(CudaModule<'a>, CUfunction), TestError> {
+    let module = load_module(ctx, t)?;
+    let kernel = ctx
+        .driver()
+        .get_function(module.value, c"run")
         .map_err(|_| TestError::MissingRunFunction)?;
+    Ok((module, kernel))
+}
 
-    let mut rng = XorShiftRng::seed_from_u64(SEED);
-    let mut free_memory = 0;
-    let mut total_memory = 0;
-    unsafe { cuda.cuMemGetInfo_v2(&mut free_memory, &mut total_memory) }.unwrap();
-    let max_memory = (total_memory / 2).min(SAFE_MEMORY_LIMIT);
-    let total_elements = 2.pow(32);
-    assert!(total_elements % GROUP_SIZE == 0);
+/// Number of cases in a single batch, so that `PIPELINE_DEPTH` batches fit in device memory.
+fn batch_capacity<Test: TestCommon>(
+    driver: &dyn Driver,
+    total_elements: usize,
+) -> Result<usize, TestError> {
+    let (_, total_memory) = driver!(driver, mem_info())?;
+    let max_memory = (total_memory / 2).min(SAFE_MEMORY_LIMIT) / PIPELINE_DEPTH;
     let element_size = Test::Input::size_of() + Test::Output::size_of();
     let required_memory = total_elements * element_size;
     let iterations = (required_memory / max_memory).max(1);
-    let memory_batch_size: usize =
-        next_multiple_of(required_memory / iterations, GROUP_SIZE * element_size);
-    let mut inputs = vec![Vec::new(); Test::Input::COMPONENTS];
-    let mut outputs = vec![Test::Output::zero(); memory_batch_size / element_size];
-
-    let mut first_error = None;
-    let mut total_cases = 0;
-    let mut passed_cases = 0;
+    Ok(next_multiple_of(required_memory / iterations, GROUP_SIZE * element_size) / element_size)
+}
+
+/// Run `cases` cases of a test, split into batches that fit in device memory.
+///
+/// Batches are pipelined: while the GPU executes one batch, the host generates the inputs of
+/// the next one and verifies the outputs of the previous one. `generate` is called once for
+/// every case, in order, and must write the input of the case with the given index.
+///
+/// Kernels are launched for whole groups of `GROUP_SIZE` cases, the last group is filled up by
+/// repeating the last case, which is not verified again.
+fn run_batches<Test: TestCommon>(
+    ctx: &dyn TestContext,
+    t: &Test,
+    opts: &TestOptions,
+    seed: Option<u64>,
+    cases: usize,
+    mut generate: impl FnMut(usize, &mut [Vec<u8>]),
+) -> Result<(), TestError> {
+    assert_eq!(Test::Output::COMPONENTS, 1);
+    let driver = ctx.driver();
+    let total_elements = cases.next_multiple_of(GROUP_SIZE);
+    let mut generate =
+        |index: usize, inputs: &mut [Vec<u8>]| generate(index.min(cases - 1), inputs);
+
+    let (_module, kernel) = load_kernel(ctx, t)?;
+    let batch_elements = batch_capacity::<Test>(driver, total_elements)?;
+
+    let batch_starts: Vec<_> = (0..total_elements).step_by(batch_elements).collect();
+    let mut batches: Vec<_> = (0..PIPELINE_DEPTH.min(batch_starts.len()))
+        .map(|_| Batch::new::<Test::Input, Test::Output>(driver, batch_elements))
+        .collect::<Result<_, _>>()?;
+    let key = GoldenKey::new(
+        &ctx.golden_source(t),
+        seed,
+        total_elements,
+        Test::Output::size_of(),
+    );
+    let mut golden = GoldenFile::open(opts.golden.as_ref(), &key)?;
+    let mut verification = Verification::new(opts, cases);
+
+    for (iteration, element_start) in batch_starts.iter().copied().enumerate() {
+        let batch = &mut batches[iteration % PIPELINE_DEPTH];
+        let len = batch_elements.min(total_elements - element_start);
+        batch.prepare(element_start, len, &mut generate);
+        batch.submit(kernel)?;
+        // The GPU is now busy with this batch, verify the previous one in the meantime
+        if iteration > 0 {
+            let previous = &mut batches[(iteration - 1) % PIPELINE_DEPTH];
+            previous.wait()?;
+            let expected = golden.next_batch(&previous.outputs)?;
+            verification.verify_batch(
+                t,
+                &previous.inputs,
+                &previous.outputs,
+                expected.as_ref().map(|e| e.as_slice()),
+                previous.first_index,
+            );
+        }
+    }
+    if let Some(last) = batch_starts.len().checked_sub(1) {
+        let batch = &mut batches[last % PIPELINE_DEPTH];
+        batch.wait()?;
+        let expected = golden.next_batch(&batch.outputs)?;
+        verification.verify_batch(
+            t,
+            &batch.inputs,
+            &batch.outputs,
+            expected.as_ref().map(|e| e.as_slice()),
+            batch.first_index,
+        );
+    }
+    golden.finish()?;
+
+    verification.into_result()
+}
+
+pub fn diff_random<Test: RandomTest>(
+    ctxs: [&dyn TestContext; 2],
+    t: &Test,
+    opts: &TestOptions,
+) -> Result<DiffResult, String> {
+    let mut rng = XorShiftRng::seed_from_u64(opts.seed);
+    let total_elements = opts.random_cases.unwrap_or(Test::CASES);
+    assert!(total_elements.is_multiple_of(GROUP_SIZE));
+    run_diff(ctxs, t, opts, total_elements, |_, inputs| {
+        t.generate(&mut rng).write(inputs)
+    })
+}
+
+pub fn diff_range<Test: RangeTest>(
+    ctxs: [&dyn TestContext; 2],
+    t: &Test,
+    opts: &TestOptions,
+) -> Result<DiffResult, String> {
+    let cases = t.max_value() as usize + 1;
+    run_diff(ctxs, t, opts, cases, |index, inputs| {
+        t.generate(index as u32).write(inputs)
+    })
+}
+
+/// Names of the two libraries compared by `run_diff`, in the order they are passed
+pub const DIFF_SIDES: [&str; 2] = ["A", "B"];
+
+/// Run the same `cases` cases on two drivers and compare their outputs bit for bit, padded like
+/// in `run_batches`.
+///
+/// `host_verify` is not used to pass or fail the test, it only tells which side is right.
+fn run_diff<Test: TestCommon>(
+    ctxs: [&dyn TestContext; 2],
+    t: &Test,
+    opts: &TestOptions,
+    cases: usize,
+    mut generate: impl FnMut(usize, &mut [Vec<u8>]),
+) -> Result<DiffResult, String> {
+    assert_eq!(Test::Output::COMPONENTS, 1);
+    let total_elements = cases.next_multiple_of(GROUP_SIZE);
+    let mut generate =
+        |index: usize, inputs: &mut [Vec<u8>]| generate(index.min(cases - 1), inputs);
+    let on_side = |side: usize| move |err: TestError| format!("{}: {err}", DIFF_SIDES[side]);
+
+    let (_module_a, kernel_a) = load_kernel(ctxs[0], t).map_err(on_side(0))?;
+    let (_module_b, kernel_b) = load_kernel(ctxs[1], t).map_err(on_side(1))?;
+    let batch_elements = batch_capacity::<Test>(ctxs[0].driver(), total_elements)
+        .map_err(on_side(0))?
+        .min(batch_capacity::<Test>(ctxs[1].driver(), total_elements).map_err(on_side(1))?);
+    let mut batch_a = Batch::new::<Test::Input, Test::Output>(ctxs[0].driver(), batch_elements)
+        .map_err(on_side(0))?;
+    let mut batch_b = Batch::new::<Test::Input, Test::Output>(ctxs[1].driver(), batch_elements)
+        .map_err(on_side(1))?;
+    let mut result = DiffResult::default();
+
+    for element_start in (0..total_elements).step_by(batch_elements) {
+        let len = batch_elements.min(total_elements - element_start);
+        batch_a.prepare(element_start, len, &mut generate);
+        batch_b.prepare_from(&batch_a);
+        batch_a.submit(kernel_a).map_err(on_side(0))?;
+        batch_b.submit(kernel_b).map_err(on_side(1))?;
+        batch_a.wait().map_err(on_side(0))?;
+        batch_b.wait().map_err(on_side(1))?;
+        result.compare_batch(
+            t,
+            opts,
+            &batch_a.inputs,
+            [&batch_a.outputs, &batch_b.outputs],
+            batch_a.first_index,
+            cases,
+        );
+        if opts.fail_fast && result.differing_cases > 0 {
+            break;
+        }
+    }
 
-    for iteration in 0..iterations {
-        assert_eq!(Test::Output::COMPONENTS, 1);
-        let memory_batch_size = if iteration == iterations - 1 {
-            required_memory - (memory_batch_size * (iterations - 1))
+    Ok(result)
+}
+
+/// Outcome of comparing the outputs of two drivers, see `run_diff`.
+#[derive(Debug, Default)]
+pub struct DiffResult {
+    pub total_cases: usize,
+    /// Cases where the outputs of the two sides are not bit for bit identical
+    pub differing_cases: usize,
+    /// Cases where the output of each side is rejected by `host_verify`
+    pub reference_failures: [usize; 2],
+    /// First differing cases, up to `--max-samples`
+    pub samples: Vec<DiffSample>,
+}
+
+#[derive(Debug)]
+pub struct DiffSample {
+    pub index: usize,
+    pub input: String,
+    pub outputs: [String; 2],
+    /// Whether `host_verify` accepts the output of each side
+    pub passes: [bool; 2],
+}
+
+impl DiffResult {
+    /// Compare the outputs of a batch on up to `opts.jobs` threads, out of `cases` cases in total.
+    fn compare_batch<Test: TestCommon>(
+        &mut self,
+        t: &Test,
+        opts: &TestOptions,
+        inputs: &[Vec<u8>],
+        outputs: [&[Vec<u8>]; 2],
+        first_index: usize,
+        cases: usize,
+    ) {
+        // Padding after the last case is not compared
+        let len = (outputs[0][0].len() / Test::Output::size_of()).min(cases - first_index);
+        let chunks = len.div_ceil(VERIFY_CHUNK_SIZE);
+        let compare_chunk = |chunk: usize| {
+            let start = chunk * VERIFY_CHUNK_SIZE;
+            let end = (start + VERIFY_CHUNK_SIZE).min(len);
+            Self::compare_chunk(t, opts, inputs, outputs, start..end, first_index)
+        };
+        let jobs = opts.jobs.clamp(1, chunks.max(1));
+        let results: Vec<_> = if jobs == 1 {
+            (0..chunks).map(compare_chunk).collect()
         } else {
-            memory_batch_size
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..jobs)
+                    .map(|job| {
+                        scope.spawn(move || {
+                            (job..chunks)
+                                .step_by(jobs)
+                                .map(|chunk| (chunk, compare_chunk(chunk)))
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                let mut results: Vec<_> = workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap())
+                    .collect();
+                results.sort_unstable_by_key(|(chunk, _)| *chunk);
+                results.into_iter().map(|(_, result)| result).collect()
+            })
         };
-        let element_batch_size = memory_batch_size / element_size;
-        for vec in inputs.iter_mut() {
-            vec.clear();
+        for result in results {
+            self.total_cases += result.total_cases;
+            self.differing_cases += result.differing_cases;
+            for (total, failures) in self
+                .reference_failures
+                .iter_mut()
+                .zip(result.reference_failures)
+            {
+                *total += failures;
+            }
+            let free = opts.max_samples.saturating_sub(self.samples.len());
+            self.samples.extend(result.samples.into_iter().take(free));
+        }
+    }
+
+    fn compare_chunk<Test: TestCommon>(
+        t: &Test,
+        opts: &TestOptions,
+        inputs: &[Vec<u8>],
+        [outputs_a, outputs_b]: [&[Vec<u8>]; 2],
+        range: Range<usize>,
+        first_index: usize,
+    ) -> Self {
+        let size = Test::Output::size_of();
+        let mut result = Self::default();
+        for i in range {
+            let input = Test::Input::read(inputs, i);
+            let output_a = Test::Output::read(outputs_a, i);
+            let output_b = Test::Output::read(outputs_b, i);
+            let bytes = i * size..(i + 1) * size;
+            let same = outputs_a[0][bytes.clone()] == outputs_b[0][bytes];
+            let passes_a = t.host_verify(input, output_a).is_ok();
+            let passes_b = if same {
+                passes_a
+            } else {
+                t.host_verify(input, output_b).is_ok()
+            };
+            result.total_cases += 1;
+            result.reference_failures[0] += !passes_a as usize;
+            result.reference_failures[1] += !passes_b as usize;
+            if !same {
+                result.differing_cases += 1;
+                if result.samples.len() < opts.max_samples {
+                    result.samples.push(DiffSample {
+                        index: first_index + i,
+                        input: input.debug_rich(),
+                        outputs: [output_a.debug_rich(), output_b.debug_rich()],
+                        passes: [passes_a, passes_b],
+                    });
+                }
+            }
         }
-        for _ in 0..element_batch_size {
-            t.generate(&mut rng).write(&mut inputs);
+        result
+    }
+}
+
+/// Launch a test on explicit inputs, each given as the raw bits of every component of its input.
+pub fn run_repro<Test: TestCommon>(
+    ctx: &dyn TestContext,
+    t: &Test,
+    inputs: &[Vec<u64>],
+) -> Result<Repro, String> {
+    assert_eq!(Test::Output::COMPONENTS, 1);
+    let driver = ctx.driver();
+
+    let mut buffers = vec![Vec::new(); Test::Input::COMPONENTS];
+    Test::Input::zero().write(&mut buffers);
+    let component_sizes: Vec<_> = buffers.iter().map(Vec::len).collect();
+    let mut cases = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if input.len() != component_sizes.len() {
+            return Err(format!(
+                "Expected {} components per input, got {}",
+                component_sizes.len(),
+                input.len()
+            ));
         }
-        outputs.resize(element_batch_size, Test::Output::zero());
-        let dev_inputs: Vec<_> = inputs
+        let components = input
             .iter()
-            .map(|vec| {
-                let devptr = cuda_malloc(cuda, vec.len());
-                unsafe {
-                    cuda.cuMemcpyHtoD_v2(devptr.value, vec.as_ptr().cast_mut().cast(), vec.len())
+            .zip(component_sizes.iter())
+            .map(|(&bits, &size)| {
+                if size < 8 && bits >> (size * 8) != 0 {
+                    return Err(format!("Value {bits:#X} does not fit in {size} bytes"));
                 }
-                .unwrap();
-                devptr
+                Ok(bits.to_le_bytes()[..size].to_vec())
             })
-            .collect();
-        let dev_output = cuda_malloc(cuda, element_batch_size * Test::Output::size_of());
-        let mut args = dev_inputs
+            .collect::<Result<Vec<_>, _>>()?;
+        cases.push(components);
+    }
+    if cases.is_empty() {
+        return Err("No inputs given".to_string());
+    }
+
+    let ptx = ctx.prepare_test_source(t).map_err(|err| err.to_string())?;
+    let (_module, kernel) = load_kernel(ctx, t).map_err(|err| err.to_string())?;
+
+    // Kernels run in whole groups, the last input fills up the rest of the group
+    let len = next_multiple_of(cases.len(), GROUP_SIZE);
+    let mut batch =
+        Batch::new::<Test::Input, Test::Output>(driver, len).map_err(|e| e.to_string())?;
+    batch.prepare(0, len, &mut |index, buffers| {
+        let case = &cases[index.min(cases.len() - 1)];
+        for (buffer, component) in buffers.iter_mut().zip(case.iter()) {
+            buffer.extend_from_slice(component);
+        }
+    });
+    batch.submit(kernel).map_err(|err| err.to_string())?;
+    batch.wait().map_err(|err| err.to_string())?;
+
+    let cases = (0..cases.len())
+        .map(|index| {
+            let input = Test::Input::read(&batch.inputs, index);
+            let output = Test::Output::read(&batch.outputs, index);
+            ReproCase {
+                input: ReproValue::new(&input),
+                output: ReproValue::new(&output),
+                expected: t
+                    .host_verify(input, output)
+                    .err()
+                    .map(|expected| ReproValue::new(&expected)),
+            }
+        })
+        .collect();
+    Ok(Repro {
+        ptx: ptx.to_string_lossy().into_owned(),
+        cases,
+    })
+}
+
+/// Results of launching a test on explicit inputs, see `run_repro`.
+pub struct Repro {
+    /// Source loaded into the driver
+    pub ptx: String,
+    pub cases: Vec<ReproCase>,
+}
+
+pub struct ReproCase {
+    pub input: ReproValue,
+    pub output: ReproValue,
+    /// Result computed on the host, `None` when it matches the output
+    pub expected: Option<ReproValue>,
+}
+
+pub struct ReproValue {
+    pub debug: String,
+    pub rich: String,
+}
+
+impl ReproValue {
+    fn new<T: Debug + DebugRich>(value: &T) -> Self {
+        ReproValue {
+            debug: format!("{value:?}"),
+            rich: value.debug_rich(),
+        }
+    }
+}
+
+/// Host and device memory of a single batch in flight.
+///
+/// Inputs are generated into `inputs` and outputs are read back into `outputs`, both are
+/// staged through page-locked memory, so that copies run asynchronously on the batch's stream.
+struct Batch<'a> {
+    driver: &'a dyn Driver,
+    stream: CudaStream<'a>,
+    dev_inputs: Vec<DevicePtr<'a>>,
+    dev_output: DevicePtr<'a>,
+    staging_inputs: Vec<HostPtr<'a>>,
+    staging_output: HostPtr<'a>,
+    inputs: Vec<Vec<u8>>,
+    outputs: Vec<Vec<u8>>,
+    output_size: usize,
+    first_index: usize,
+    len: usize,
+}
+
+impl<'a> Batch<'a> {
+    fn new<Input: OnDevice, Output: OnDevice>(
+        driver: &'a dyn Driver,
+        capacity: usize,
+    ) -> Result<Self, TestError> {
+        // Write a single value to learn the size of each component
+        let mut inputs = vec![Vec::new(); Input::COMPONENTS];
+        Input::zero().write(&mut inputs);
+        let input_sizes: Vec<_> = inputs.iter().map(Vec::len).collect();
+        let output_size = Output::size_of();
+        Ok(Batch {
+            driver,
+            stream: cuda_stream(driver)?,
+            dev_inputs: input_sizes
+                .iter()
+                .map(|size| cuda_malloc(driver, size * capacity))
+                .collect::<Result<_, _>>()?,
+            dev_output: cuda_malloc(driver, output_size * capacity)?,
+            staging_inputs: input_sizes
+                .iter()
+                .map(|size| cuda_malloc_host(driver, size * capacity))
+                .collect::<Result<_, _>>()?,
+            staging_output: cuda_malloc_host(driver, output_size * capacity)?,
+            inputs: input_sizes
+                .iter()
+                .map(|size| Vec::with_capacity(size * capacity))
+                .collect(),
+            outputs: vec![Vec::with_capacity(output_size * capacity)],
+            output_size,
+            first_index: 0,
+            len: 0,
+        })
+    }
+
+    fn prepare(
+        &mut self,
+        first_index: usize,
+        len: usize,
+        generate: &mut impl FnMut(usize, &mut [Vec<u8>]),
+    ) {
+        self.first_index = first_index;
+        self.len = len;
+        for vec in self.inputs.iter_mut() {
+            vec.clear();
+        }
+        for index in first_index..first_index + len {
+            generate(index, &mut self.inputs);
+        }
+    }
+
+    /// Use the same cases as `other`, which may belong to a different driver.
+    fn prepare_from(&mut self, other: &Batch) {
+        self.first_index = other.first_index;
+        self.len = other.len;
+        for (vec, other) in self.inputs.iter_mut().zip(other.inputs.iter()) {
+            vec.clone_from(other);
+        }
+    }
+
+    fn submit(&mut self, kernel: CUfunction) -> Result<(), TestError> {
+        let driver = self.driver;
+        for ((vec, staging), devptr) in self
+            .inputs
             .iter()
-            .map(|dev_ptr| &dev_ptr.value as *const u64)
-            .collect::<Vec<_>>();
-        args.push(&dev_output.value);
-        unsafe {
-            cuda.cuLaunchKernel(
+            .zip(self.staging_inputs.iter())
+            .zip(self.dev_inputs.iter())
+        {
+            unsafe { ptr::copy_nonoverlapping(vec.as_ptr(), staging.value, vec.len()) };
+            unsafe {
+                driver!(
+                    driver,
+                    memcpy_htod_async(devptr.value, staging.value, vec.len(), self.stream.value)
+                )?;
+            }
+        }
+        let mut params: Vec<_> = self.dev_inputs.iter().map(|ptr| ptr.value).collect();
+        params.push(self.dev_output.value);
+        driver!(
+            driver,
+            launch_kernel(
                 kernel,
-                (element_batch_size / GROUP_SIZE) as u32,
-                1,
-                1,
+                (self.len / GROUP_SIZE) as u32,
                 GROUP_SIZE as u32,
-                1,
-                1,
-                0,
-                0 as _,
-                args.as_mut_ptr() as _,
-                ptr::null_mut(),
+                self.stream.value,
+                &params,
             )
-        }
-        .unwrap();
-        unsafe { cuda.cuStreamSynchronize(0 as _) }.unwrap();
+        )?;
         unsafe {
-            cuda.cuMemcpyDtoH_v2(
-                outputs.as_mut_ptr() as _,
-                dev_output.value,
-                outputs.len() * Test::Output::size_of(),
+            driver!(
+                driver,
+                memcpy_dtoh_async(
+                    self.staging_output.value,
+                    self.dev_output.value,
+                    self.len * self.output_size,
+                    self.stream.value,
+                )
             )
         }
-        .unwrap();
-        for (i, output) in outputs.iter().copied().enumerate() {
-            let input = Test::Input::read(&inputs, i);
-            total_cases += 1;
-            if let Err(expected) = t.host_verify(input, output) {
-                first_error.get_or_insert((input, output, expected));
-                if fail_fast {
-                    break;
-                }
-            } else {
-                passed_cases += 1;
-            }
-        }
     }
 
-    if let Some((input, output, expected)) = first_error {
-        Err(TestError::ResultMismatch {
-            input: input.debug_rich(),
-            output: output.debug_rich(),
-            expected: expected.debug_rich(),
-            total_cases,
-            passed_cases,
-        })
-    } else {
+    fn wait(&mut self) -> Result<(), TestError> {
+        driver!(self.driver, stream_synchronize(self.stream.value))?;
+        let output = &mut self.outputs[0];
+        output.clear();
+        let staging = unsafe {
+            slice::from_raw_parts(self.staging_output.value, self.len * self.output_size)
+        };
+        output.extend_from_slice(staging);
         Ok(())
     }
 }
 
-fn cuda_malloc<'a>(cuda: &'a Cuda, size: usize) -> DevicePtr<'a> {
-    let mut value = 0;
-    unsafe { cuda.cuMemAlloc_v2(&mut value, size) }.unwrap();
-    DevicePtr { cuda, value }
+fn cuda_malloc(driver: &dyn Driver, size: usize) -> Result<DevicePtr<'_>, TestError> {
+    let value = driver!(driver, mem_alloc(size))?;
+    Ok(DevicePtr { driver, value })
+}
+
+fn cuda_malloc_host(driver: &dyn Driver, size: usize) -> Result<HostPtr<'_>, TestError> {
+    let value = driver!(driver, mem_alloc_host(size))?;
+    Ok(HostPtr { driver, value })
+}
+
+fn cuda_stream(driver: &dyn Driver) -> Result<CudaStream<'_>, TestError> {
+    let value = driver!(driver, stream_create())?;
+    Ok(CudaStream { driver, value })
 }
 
 fn next_multiple_of(value: usize, multiple: usize) -> usize {
     ((value + multiple - 1) / multiple) * multiple
 }
 
-pub fn run_range<Test: RangeTest>(
-    ctx: &dyn TestContext,
-    t: Test,
-    fail_fast: bool,
-) -> Result<(), TestError> {
-    let cuda = ctx.cuda();
-
-    let module = load_module(ctx, &t)?;
-    let mut kernel = ptr::null_mut();
-    unsafe { cuda.cuModuleGetFunction(&mut kernel, module.value, c"run".as_ptr()) }
-        .map_err(|_| TestError::MissingRunFunction)?;
+/// Verification state shared by all batches of a single test.
+struct Verification<'a, Input, Output> {
+    opts: &'a TestOptions,
+    /// Number of cases of the test, outputs of padding after them are not verified
+    cases: usize,
+    total_cases: usize,
+    passed_cases: usize,
+    mismatches: MismatchSampler<Input, Output>,
+}
 
-    let mut free_memory = 0;
-    let mut total_memory = 0;
-    unsafe { cuda.cuMemGetInfo_v2(&mut free_memory, &mut total_memory) }.unwrap();
-    let max_memory = (total_memory / 2).min(SAFE_MEMORY_LIMIT);
-    let total_elements = Test::MAX_VALUE as usize + 1;
-    if total_elements % GROUP_SIZE != 0 {
-        panic!(
-            "Total element count {} must be divisble by {}",
-            total_elements, GROUP_SIZE
-        );
+impl<'a, Input, Output> Verification<'a, Input, Output>
+where
+    Input: OnDevice + DebugRich,
+    Output: OnDevice + DebugRich,
+{
+    fn new(opts: &'a TestOptions, cases: usize) -> Self {
+        Self {
+            opts,
+            cases,
+            total_cases: 0,
+            passed_cases: 0,
+            mismatches: MismatchSampler::new(opts.max_samples),
+        }
     }
-    let element_size = Test::Input::size_of() + Test::Output::size_of();
-    let required_memory = total_elements * element_size;
-    let iterations = (required_memory / max_memory).max(1);
-    let memory_batch_size: usize =
-        next_multiple_of(required_memory / iterations, GROUP_SIZE * element_size);
-    let mut inputs = vec![Vec::new(); Test::Input::COMPONENTS];
-    let mut outputs = vec![Test::Output::zero(); memory_batch_size / element_size];
-
-    let mut first_error = None;
-    let mut total_cases = 0;
-    let mut passed_cases = 0;
 
-    for iteration in 0..iterations {
-        assert_eq!(Test::Output::COMPONENTS, 1);
-        let elment_start = iteration * memory_batch_size / element_size;
-        let memory_batch_size = if iteration == iterations - 1 {
-            required_memory - (memory_batch_size * (iterations - 1))
+    /// Verify a batch of outputs on up to `opts.jobs` threads.
+    ///
+    /// Outputs are checked with `host_verify`, or compared bit for bit if `expected` is given.
+    ///
+    /// The batch is split into chunks of a fixed size, independent of the number of threads. Chunks
+    /// only record which of their cases failed, the failures are then sampled in order, so the
+    /// sample is the same as with a single thread for any `--jobs`.
+    fn verify_batch<Test: TestCommon<Input = Input, Output = Output>>(
+        &mut self,
+        t: &Test,
+        inputs: &[Vec<u8>],
+        outputs: &[Vec<u8>],
+        expected: Option<&[Vec<u8>]>,
+        first_index: usize,
+    ) {
+        let len = (outputs[0].len() / Output::size_of()).min(self.cases - first_index);
+        let chunks = len.div_ceil(VERIFY_CHUNK_SIZE);
+        let next_chunk = AtomicUsize::new(0);
+        // With --fail-fast there is no point in verifying chunks after the first failing one
+        let first_failed_chunk = AtomicUsize::new(usize::MAX);
+        let opts = self.opts;
+        let verify_chunks = || {
+            let mut results = Vec::new();
+            loop {
+                let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if chunk >= chunks || chunk > first_failed_chunk.load(Ordering::Relaxed) {
+                    break;
+                }
+                let start = chunk * VERIFY_CHUNK_SIZE;
+                let end = (start + VERIFY_CHUNK_SIZE).min(len);
+                let result = Self::verify_chunk(opts, t, inputs, outputs, expected, start..end);
+                if opts.fail_fast && result.failed() {
+                    first_failed_chunk.fetch_min(chunk, Ordering::Relaxed);
+                }
+                results.push((chunk, result));
+            }
+            results
+        };
+        let jobs = opts.jobs.clamp(1, chunks.max(1));
+        let mut results = if jobs == 1 {
+            verify_chunks()
         } else {
-            memory_batch_size
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..jobs).map(|_| scope.spawn(verify_chunks)).collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap())
+                    .collect()
+            })
         };
-        let element_batch_size = memory_batch_size / element_size;
-        for vec in inputs.iter_mut() {
-            vec.clear();
-        }
-        for i in 0..element_batch_size {
-            let input = t.generate((elment_start + i) as u32);
-            input.write(&mut inputs);
+        results.sort_unstable_by_key(|(chunk, _)| *chunk);
+        if self.opts.fail_fast {
+            if let Some(failed) = results.iter().position(|(_, result)| result.failed()) {
+                results.truncate(failed + 1);
+            }
         }
-        outputs.resize(element_batch_size, Test::Output::zero());
-        let dev_inputs: Vec<_> = inputs
+        let last_failure = results
             .iter()
-            .map(|vec| {
-                let devptr = cuda_malloc(cuda, vec.len());
-                unsafe {
-                    cuda.cuMemcpyHtoD_v2(devptr.value, vec.as_ptr().cast_mut().cast(), vec.len())
+            .rev()
+            .find_map(|(chunk, result)| Some(chunk * VERIFY_CHUNK_SIZE + result.last_failure()?));
+        for (chunk, result) in results {
+            self.total_cases += result.total_cases;
+            self.passed_cases += result.passed_cases;
+            for i in result.failures(chunk * VERIFY_CHUNK_SIZE) {
+                // Only failures that end up in the sample are read again
+                if !self.mismatches.samples_next() && Some(i) != last_failure {
+                    self.mismatches.skip();
+                    continue;
                 }
-                .unwrap();
-                devptr
-            })
-            .collect();
-        let dev_output = cuda_malloc(cuda, element_batch_size * Test::Output::size_of());
-        let mut args = dev_inputs
-            .iter()
-            .map(|ptr| &ptr.value as *const u64)
-            .collect::<Vec<_>>();
-        args.push(&dev_output.value);
-        unsafe {
-            cuda.cuLaunchKernel(
-                kernel,
-                (element_batch_size / GROUP_SIZE) as u32,
-                1,
-                1,
-                GROUP_SIZE as u32,
-                1,
-                1,
-                0,
-                0 as _,
-                args.as_mut_ptr() as _,
-                ptr::null_mut(),
-            )
-        }
-        .unwrap();
-        unsafe { cuda.cuStreamSynchronize(0 as _) }.unwrap();
-        unsafe {
-            cuda.cuMemcpyDtoH_v2(
-                outputs.as_mut_ptr() as _,
-                dev_output.value,
-                outputs.len() * Test::Output::size_of(),
-            )
+                let input = Input::read(inputs, i);
+                let output = Output::read(outputs, i);
+                let expected = match expected {
+                    Some(expected) => Output::read(expected, i),
+                    None => t.host_verify(input, output).unwrap_err(),
+                };
+                self.mismatches
+                    .push(first_index + i, input, output, expected);
+            }
         }
-        .unwrap();
-        for (i, output) in outputs.iter().copied().enumerate() {
-            let input = Test::Input::read(&inputs, i);
-            total_cases += 1;
-            if let Err(expected) = t.host_verify(input, output) {
-                first_error.get_or_insert((input, output, expected));
-                if fail_fast {
+    }
+
+    fn verify_chunk<Test: TestCommon<Input = Input, Output = Output>>(
+        opts: &'a TestOptions,
+        t: &Test,
+        inputs: &[Vec<u8>],
+        outputs: &[Vec<u8>],
+        expected: Option<&[Vec<u8>]>,
+        range: Range<usize>,
+    ) -> ChunkResult {
+        let size = Output::size_of();
+        let mut result = ChunkResult {
+            total_cases: 0,
+            passed_cases: 0,
+            failures: vec![0; range.len().div_ceil(64)],
+        };
+        for (position, i) in range.enumerate() {
+            let input = Input::read(inputs, i);
+            let output = Output::read(outputs, i);
+            result.total_cases += 1;
+            let verified = match expected {
+                Some(expected) => {
+                    let bytes = i * size..(i + 1) * size;
+                    if outputs[0][bytes.clone()] == expected[0][bytes] {
+                        Ok(())
+                    } else {
+                        Err(Output::read(expected, i))
+                    }
+                }
+                None => t.host_verify(input, output),
+            };
+            if verified.is_err() {
+                result.failures[position / 64] |= 1 << (position % 64);
+                if opts.fail_fast {
                     break;
                 }
             } else {
-                passed_cases += 1;
+                result.passed_cases += 1;
             }
         }
+        result
     }
 
-    if let Some((input, output, expected)) = first_error {
-        Err(TestError::ResultMismatch {
+    fn into_result(self) -> Result<(), TestError> {
+        let Some((_, input, output, expected)) = self.mismatches.first else {
+            return Ok(());
+        };
+        Err(TestError::ResultMismatch(Box::new(Mismatch {
             input: input.debug_rich(),
             output: output.debug_rich(),
             expected: expected.debug_rich(),
-            total_cases,
-            passed_cases,
-        })
-    } else {
-        Ok(())
+            total_cases: self.total_cases,
+            passed_cases: self.passed_cases,
+            samples: self.mismatches.into_samples(),
+            seed: None,
+            golden: matches!(self.opts.golden, Some(golden::Golden::Compare(_))),
+        })))
     }
 }
 
-pub type TestFunction = Box<dyn FnOnce(&dyn TestContext, bool) -> Result<(), TestError>>;
+/// Cases of a chunk verified by `Verification::verify_batch`.
+struct ChunkResult {
+    total_cases: usize,
+    passed_cases: usize,
+    /// Bit set of the positions of failing cases within the chunk
+    failures: Vec<u64>,
+}
+
+impl ChunkResult {
+    fn failed(&self) -> bool {
+        self.passed_cases != self.total_cases
+    }
+
+    fn last_failure(&self) -> Option<usize> {
+        let (word, bits) = self
+            .failures
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, bits)| **bits != 0)?;
+        Some(word * 64 + 63 - bits.leading_zeros() as usize)
+    }
 
-pub fn make_random<T: RandomTest + 'static>(t: T) -> TestFunction {
-    return Box::new(move |ctx, fail_fast| run_random::<T>(ctx, t, fail_fast));
+    /// Indices of the failing cases in the batch, in order, given the index of the first case of
+    /// the chunk.
+    fn failures(&self, start: usize) -> impl Iterator<Item = usize> + '_ {
+        self.failures
+            .iter()
+            .enumerate()
+            .flat_map(move |(word, &bits)| {
+                (0..64)
+                    .filter(move |bit| bits & (1 << bit) != 0)
+                    .map(move |bit| start + word * 64 + bit)
+            })
+    }
 }
 
-pub fn make_range<T: RangeTest + 'static>(t: T) -> TestFunction {
-    return Box::new(move |ctx, fail_fast| run_range::<T>(ctx, t, fail_fast));
+/// Bounded sample of failing cases, evenly spread over all failures of a test.
+///
+/// Every `stride`-th failure is kept and the stride doubles whenever the sample outgrows its
+/// limit, so the first failure is always part of the sample. The last failure is tracked
+/// separately and appended at the end.
+struct MismatchSampler<Input, Output> {
+    limit: usize,
+    keep_last: bool,
+    stride: usize,
+    seen: usize,
+    first: Option<(usize, Input, Output, Output)>,
+    last: Option<(usize, Input, Output, Output)>,
+    samples: Vec<(usize, Input, Output, Output)>,
+}
+
+impl<Input: DebugRich + Copy, Output: DebugRich + Copy> MismatchSampler<Input, Output> {
+    fn new(max_samples: usize) -> Self {
+        // Reserve one slot for the last failure if there is room for more than the first one
+        let keep_last = max_samples > 1;
+        Self {
+            limit: if keep_last {
+                max_samples - 1
+            } else {
+                max_samples
+            },
+            keep_last,
+            stride: 1,
+            seen: 0,
+            first: None,
+            last: None,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Whether the next failure is kept, either as the first one or as a sample. Failures that are
+    /// not kept can be counted with `skip` instead of `push`.
+    fn samples_next(&self) -> bool {
+        self.first.is_none() || (self.limit > 0 && self.seen.is_multiple_of(self.stride))
+    }
+
+    fn skip(&mut self) {
+        self.seen += 1;
+    }
+
+    fn push(&mut self, index: usize, input: Input, output: Output, expected: Output) {
+        let failure = (index, input, output, expected);
+        self.first.get_or_insert(failure);
+        if self.limit > 0 && self.seen.is_multiple_of(self.stride) {
+            self.samples.push(failure);
+            if self.samples.len() > self.limit {
+                let mut position = 0;
+                self.samples.retain(|_| {
+                    position += 1;
+                    position % 2 == 1
+                });
+                self.stride *= 2;
+            }
+        }
+        self.seen += 1;
+        self.last = Some(failure);
+    }
+
+    fn into_samples(self) -> Vec<MismatchSample> {
+        let mut samples = self.samples;
+        if let Some(last) = self.last {
+            if self.keep_last && samples.last().map(|s| s.0) != Some(last.0) {
+                samples.push(last);
+            }
+        }
+        samples
+            .into_iter()
+            .map(|(index, input, output, expected)| MismatchSample {
+                index,
+                input: input.debug_rich(),
+                output: output.debug_rich(),
+                expected: expected.debug_rich(),
+            })
+            .collect()
+    }
+}
+
+/// Options controlling the execution of every test in a run.
+#[derive(Debug, Clone)]
+pub struct TestOptions {
+    /// Stop verifying a test on its first failing case
+    pub fail_fast: bool,
+    /// Maximum number of failing cases reported per test
+    pub max_samples: usize,
+    /// Number of threads used for host-side verification
+    pub jobs: usize,
+    /// Seed of the random number generator used by `RandomTest`s
+    pub seed: u64,
+    /// Overrides `RandomTest::CASES` for every test
+    pub random_cases: Option<usize>,
+    /// Golden outputs of this test, set separately for every test
+    pub golden: Option<golden::Golden>,
+}
+
+/// A test with its input and output types erased.
+pub trait AnyTest {
+    fn run(&self, ctx: &dyn TestContext, opts: &TestOptions) -> Result<(), TestError>;
+    fn repro(&self, ctx: &dyn TestContext, inputs: &[Vec<u64>]) -> Result<Repro, String>;
+    fn diff(&self, ctxs: [&dyn TestContext; 2], opts: &TestOptions) -> Result<DiffResult, String>;
+    /// Verify outputs recorded with `--record` from `source`, `None` if the test has nothing to
+    /// replay.
+    fn replay(
+        &self,
+        path: &Path,
+        opts: &TestOptions,
+        source: &str,
+    ) -> Option<Result<(), TestError>>;
+    /// Estimated cost of running the test, used to balance shards. Computed only from the test
+    /// and `opts`, so it's the same on every machine.
+    fn cost(&self, opts: &TestOptions) -> u64;
+    fn strategy(&self) -> Strategy;
+    /// PTX the test compiles. Sets of tests return the PTX of each test in the set with its name.
+    fn ptx(&self) -> Vec<(Option<&str>, &dyn TestPtx)>;
+    /// Sizes in bytes of every input component and of the output, `None` for sets of tests
+    fn layout(&self) -> Option<(Vec<usize>, usize)>;
+    /// `TestCommon::host_verify` on raw bits, with one value for every input component
+    fn host_verify_bits(&self, input: &[u64], output: u64) -> Result<(), u64>;
+}
+
+fn layout<T: TestCommon>() -> (Vec<usize>, usize) {
+    let mut buffers = vec![Vec::new(); T::Input::COMPONENTS];
+    T::Input::zero().write(&mut buffers);
+    (buffers.iter().map(Vec::len).collect(), T::Output::size_of())
+}
+
+fn host_verify_bits<T: TestCommon>(t: &T, input: &[u64], output: u64) -> Result<(), u64> {
+    let (input_sizes, output_size) = layout::<T>();
+    let inputs: Vec<_> = input
+        .iter()
+        .zip(input_sizes)
+        .map(|(bits, size)| bits.to_le_bytes()[..size].to_vec())
+        .collect();
+    let input = T::Input::read(&inputs, 0);
+    let output = T::Output::read(&[output.to_le_bytes()[..output_size].to_vec()], 0);
+    t.host_verify(input, output).map_err(|expected| {
+        let mut outputs = vec![Vec::new()];
+        expected.write(&mut outputs);
+        let mut bits = [0; 8];
+        bits[..output_size].copy_from_slice(&outputs[0]);
+        u64::from_le_bytes(bits)
+    })
+}
+
+/// Cost of compiling and loading a single test, compared to the cost of a case which is the
+/// number of bytes it moves between host and device.
+const COMPILATION_COST: u64 = 1 << 26;
+
+fn cases_cost<T: TestCommon>(cases: u64) -> u64 {
+    let case_size = T::Input::size_of() + T::Output::size_of();
+    COMPILATION_COST + cases * case_size as u64
+}
+
+/// Tests are `Send` so that they can be handed out to threads running on different devices, and
+/// `Sync` so that tests loaded from specs can share them as host references
+pub type TestFunction = Arc<dyn AnyTest + Send + Sync>;
+
+struct RandomTestFn<T>(T);
+
+impl<T: RandomTest> AnyTest for RandomTestFn<T> {
+    fn run(&self, ctx: &dyn TestContext, opts: &TestOptions) -> Result<(), TestError> {
+        run_random(ctx, &self.0, opts)
+    }
+
+    fn repro(&self, ctx: &dyn TestContext, inputs: &[Vec<u64>]) -> Result<Repro, String> {
+        run_repro(ctx, &self.0, inputs)
+    }
+
+    fn diff(&self, ctxs: [&dyn TestContext; 2], opts: &TestOptions) -> Result<DiffResult, String> {
+        diff_random(ctxs, &self.0, opts)
+    }
+
+    fn replay(
+        &self,
+        path: &Path,
+        opts: &TestOptions,
+        source: &str,
+    ) -> Option<Result<(), TestError>> {
+        Some(replay_random(&self.0, path, opts, source))
+    }
+
+    fn cost(&self, opts: &TestOptions) -> u64 {
+        cases_cost::<T>(opts.random_cases.unwrap_or(T::CASES) as u64)
+    }
+
+    fn strategy(&self) -> Strategy {
+        Strategy::Random
+    }
+
+    fn ptx(&self) -> Vec<(Option<&str>, &dyn TestPtx)> {
+        vec![(None, &self.0)]
+    }
+
+    fn layout(&self) -> Option<(Vec<usize>, usize)> {
+        Some(layout::<T>())
+    }
+
+    fn host_verify_bits(&self, input: &[u64], output: u64) -> Result<(), u64> {
+        host_verify_bits(&self.0, input, output)
+    }
+}
+
+struct RangeTestFn<T>(T);
+
+impl<T: RangeTest> AnyTest for RangeTestFn<T> {
+    fn run(&self, ctx: &dyn TestContext, opts: &TestOptions) -> Result<(), TestError> {
+        run_range(ctx, &self.0, opts)
+    }
+
+    fn repro(&self, ctx: &dyn TestContext, inputs: &[Vec<u64>]) -> Result<Repro, String> {
+        run_repro(ctx, &self.0, inputs)
+    }
+
+    fn diff(&self, ctxs: [&dyn TestContext; 2], opts: &TestOptions) -> Result<DiffResult, String> {
+        diff_range(ctxs, &self.0, opts)
+    }
+
+    fn replay(
+        &self,
+        path: &Path,
+        opts: &TestOptions,
+        source: &str,
+    ) -> Option<Result<(), TestError>> {
+        Some(replay_range(&self.0, path, opts, source))
+    }
+
+    fn cost(&self, _: &TestOptions) -> u64 {
+        cases_cost::<T>(self.0.max_value() as u64 + 1)
+    }
+
+    fn strategy(&self) -> Strategy {
+        Strategy::Range
+    }
+
+    fn ptx(&self) -> Vec<(Option<&str>, &dyn TestPtx)> {
+        vec![(None, &self.0)]
+    }
+
+    fn layout(&self) -> Option<(Vec<usize>, usize)> {
+        Some(layout::<T>())
+    }
+
+    fn host_verify_bits(&self, input: &[u64], output: u64) -> Result<(), u64> {
+        host_verify_bits(&self.0, input, output)
+    }
+}
+
+/// Tests that are all expected to fail compilation, see `TestCase::join_invalid_tests`.
+struct InvalidTests(Vec<(String, TestFunction)>);
+
+impl AnyTest for InvalidTests {
+    fn run(&self, ctx: &dyn TestContext, opts: &TestOptions) -> Result<(), TestError> {
+        use TestError::*;
+
+        for (name, test) in self.0.iter() {
+            match test.run(ctx, opts) {
+                Err(CompilationFail { .. } | MissingRunFunction) => {}
+                Ok(()) | Err(ResultMismatch(_) | DriverError { .. } | Golden { .. }) => {
+                    return Err(CompilationSuccess { name: name.clone() })
+                }
+                Err(
+                    CompilationSuccess { .. }
+                    | Crashed { .. }
+                    | Timeout { .. }
+                    | UnknownTest { .. },
+                ) => {
+                    unreachable!(
+                        "tests may not report CompilationSuccess, Crashed, Timeout or UnknownTest"
+                    )
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn repro(&self, _: &dyn TestContext, _: &[Vec<u64>]) -> Result<Repro, String> {
+        Err("Tests expected to fail compilation can't be launched".to_string())
+    }
+
+    /// There are no outputs to compare, both sides must reject every test.
+    fn diff(&self, ctxs: [&dyn TestContext; 2], opts: &TestOptions) -> Result<DiffResult, String> {
+        for (side, ctx) in ctxs.into_iter().enumerate() {
+            self.run(ctx, opts)
+                .map_err(|err| format!("{}: {err}", DIFF_SIDES[side]))?;
+        }
+        Ok(DiffResult::default())
+    }
+
+    /// Nothing is recorded for tests that don't compile.
+    fn replay(&self, _: &Path, _: &TestOptions, _: &str) -> Option<Result<(), TestError>> {
+        None
+    }
+
+    fn cost(&self, _: &TestOptions) -> u64 {
+        self.0.len() as u64 * COMPILATION_COST
+    }
+
+    fn strategy(&self) -> Strategy {
+        Strategy::Invalid
+    }
+
+    fn ptx(&self) -> Vec<(Option<&str>, &dyn TestPtx)> {
+        self.0
+            .iter()
+            .flat_map(|(name, test)| {
+                test.ptx()
+                    .into_iter()
+                    .map(|(_, ptx)| (Some(name.as_str()), ptx))
+            })
+            .collect()
+    }
+
+    fn layout(&self) -> Option<(Vec<usize>, usize)> {
+        None
+    }
+
+    fn host_verify_bits(&self, _: &[u64], _: u64) -> Result<(), u64> {
+        unreachable!("tests expected to fail compilation have no host reference")
+    }
+}
+
+pub fn make_random<T: RandomTest + Send + 'static>(t: T) -> TestFunction {
+    Arc::new(RandomTestFn(t))
+}
+
+pub fn make_range<T: RangeTest + Send + 'static>(t: T) -> TestFunction {
+    Arc::new(RangeTestFn(t))
 }
 
 pub struct TestCase {
     pub test: TestFunction,
     pub name: String,
+    /// Overrides `--timeout` for this test
+    pub timeout: Option<Duration>,
+    pub metadata: TestMetadata,
 }
 
 impl TestCase {
     pub fn new(name: String, test: TestFunction) -> Self {
-        TestCase { test, name }
+        TestCase {
+            test,
+            name,
+            timeout: None,
+            metadata: TestMetadata::default(),
+        }
     }
 
-    pub fn join_invalid_tests(name: String, tests: Vec<(String, TestFunction)>) -> Self {
-        use TestError::*;
+    pub fn with_metadata(mut self, metadata: TestMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
 
-        let test = Box::new(move |ctx: &dyn TestContext, fail_fast: bool| {
-            for (name, test) in tests {
-                match test(ctx, fail_fast) {
-                    Err(CompilationFail { .. } | MissingRunFunction) => {}
-                    Ok(()) | Err(ResultMismatch { .. }) => return Err(CompilationSuccess { name }),
-                    Err(CompilationSuccess { .. }) => {
-                        unreachable!("tests may not report CompilationSuccess")
-                    }
-                }
-            }
-            Ok(())
-        });
-        TestCase { test, name }
+    /// Kill the test if it runs for longer than `timeout`, for tests known to take much longer
+    /// than others. The test always runs in a child process, see `--timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn join_invalid_tests(name: String, tests: Vec<(String, TestFunction)>) -> Self {
+        TestCase::new(name, Arc::new(InvalidTests(tests)))
     }
 }
 
 /// Errors that a test can produce.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum TestError {
     /// Used when compilation fails, e.g. during CUDA module loading or NVRTC launch
     CompilationFail { message: String },
     /// Used when tests that should have failed compilation, succeed unexpectedly
     CompilationSuccess { name: String },
     /// Used when the test compiled successfully, but found mismatching values
-    ResultMismatch {
-        input: String,
-        output: String,
-        expected: String,
-        total_cases: usize,
-        passed_cases: usize,
-    },
+    ResultMismatch(Box<Mismatch>),
     /// Used when `cuModuleGetFunction` fails
     MissingRunFunction,
+    /// Used when the process running the test in `--isolate` mode died without reporting a result
+    Crashed {
+        signal: Option<i32>,
+        stderr_tail: String,
+    },
+    /// Used when the process running the test was killed after exceeding `--timeout`
+    Timeout { seconds: f64 },
+    /// Used when a CUDA driver call fails after the module was loaded, e.g. during kernel launch
+    DriverError {
+        call: String,
+        code: u32,
+        name: String,
+        description: String,
+    },
+    /// Used when golden outputs can't be recorded or read, e.g. because the file is missing
+    Golden { message: String },
+    /// Used when a worker of `--parallel` is asked to run a test it doesn't know
+    UnknownTest { name: String },
+}
+
+/// Details of `TestError::ResultMismatch`, boxed so that results of passing tests stay small.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mismatch {
+    pub input: String,
+    pub output: String,
+    pub expected: String,
+    pub total_cases: usize,
+    pub passed_cases: usize,
+    /// Evenly spread sample of failing cases, including the first and the last one
+    pub samples: Vec<MismatchSample>,
+    /// Seed the inputs were generated from, only set for random tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Set when `expected` is the output recorded with `--record` instead of computed on the CPU
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub golden: bool,
+}
+
+/// Single failing case of a test, formatted with `DebugRich`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MismatchSample {
+    pub index: usize,
+    pub input: String,
+    pub output: String,
+    pub expected: String,
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestError::CompilationFail { message } => write!(f, "Compilation failed:\n{message}"),
+            TestError::CompilationSuccess { name } => {
+                write!(f, "Compilation mismatch, didn't expect '{name}' to compile")
+            }
+            TestError::ResultMismatch(mismatch) => {
+                let Mismatch {
+                    input,
+                    output,
+                    expected,
+                    total_cases,
+                    passed_cases,
+                    samples,
+                    seed,
+                    golden,
+                } = &**mismatch;
+                let percent = (*passed_cases as f32 / *total_cases as f32) * 100f32;
+                let source = if *golden {
+                    "recorded golden output"
+                } else {
+                    "computed on CPU"
+                };
+                write!(
+                    f,
+                    "with input {input}\n    computed on GPU: {output}\n    {source}: {expected}\n    passed: {passed_cases} out of {total_cases} ({percent}%)"
+                )?;
+                if let Some(seed) = seed {
+                    write!(f, "\n    random seed: {seed}")?;
+                }
+                if samples.len() > 1 {
+                    write!(f, "\n    sampled failures:")?;
+                    for sample in samples {
+                        write!(
+                            f,
+                            "\n    case {}: input {}\n        computed on GPU: {}\n        {source}: {}",
+                            sample.index, sample.input, sample.output, sample.expected
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+            TestError::MissingRunFunction => write!(f, "Missing run function"),
+            TestError::Crashed {
+                signal,
+                stderr_tail,
+            } => {
+                match signal {
+                    Some(signal) => write!(f, "Crashed with signal {signal}")?,
+                    None => write!(f, "Crashed")?,
+                }
+                if !stderr_tail.is_empty() {
+                    write!(f, ", last lines of stderr:\n{stderr_tail}")?;
+                }
+                Ok(())
+            }
+            TestError::Timeout { seconds } => write!(f, "Timed out after {seconds} seconds"),
+            TestError::DriverError {
+                call,
+                code,
+                name,
+                description,
+            } => write!(f, "{call} failed with {name} ({code}): {description}"),
+            TestError::Golden { message } => write!(f, "Golden outputs: {message}"),
+            TestError::UnknownTest { name } => write!(f, "No test named '{name}'"),
+        }
+    }
 }
@@ -1,12 +1,15 @@
 use crate::common::flush_to_zero_f32;
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("abs.ptx");
 
 pub fn all_tests() -> Vec<TestCase> {
     vec![
-        TestCase::new("abs".to_string(), make_range(Abs { ftz: false })),
-        TestCase::new("abs_ftz".to_string(), make_range(Abs { ftz: true })),
+        TestCase::new("abs".to_string(), make_range(Abs { ftz: false }))
+            .with_metadata(TestMetadata::new("abs").types(["f32"])),
+        TestCase::new("abs_ftz".to_string(), make_range(Abs { ftz: true }))
+            .with_metadata(TestMetadata::new("abs").types(["f32"]).modifiers(["ftz"])),
     ]
 }
 
@@ -20,8 +23,8 @@ impl TestPtx for Abs {
         PTX.replace("<FTZ>", ftz)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{
     make_range, PtxScalar, RangeTest, TestCase, TestCommon, TestPtx,
 };
@@ -35,9 +36,12 @@ where
 
 pub fn all_tests() -> Vec<TestCase> {
     vec![
-        TestCase::new("add_u16".to_string(), make_range(AddTest::<u16>::default())),
-        TestCase::new("add_i16".to_string(), make_range(AddTest::<i16>::default())),
-        TestCase::new("add_sat_s32".to_string(), make_range(AddSatTest::default())),
+        TestCase::new("add_u16".to_string(), make_range(AddTest::<u16>::default()))
+            .with_metadata(TestMetadata::new("add").types(["u16"])),
+        TestCase::new("add_i16".to_string(), make_range(AddTest::<i16>::default()))
+            .with_metadata(TestMetadata::new("add").types(["s16"])),
+        TestCase::new("add_sat_s32".to_string(), make_range(AddSatTest::default()))
+            .with_metadata(TestMetadata::new("add").types(["s32"]).modifiers(["sat"])),
     ]
 }
 
@@ -53,8 +57,8 @@ impl<T: PtxScalar> TestPtx for AddTest<T> {
            .replace("<TYPE_SIZE>", &mem::size_of::<T>().to_string())
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -95,8 +99,8 @@ impl TestPtx for AddSatTest {
            .replace("<TYPE_SIZE>", "4")
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
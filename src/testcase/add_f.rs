@@ -1,4 +1,5 @@
 use crate::common;
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestCase, TestCommon, TestPtx};
 use rand::Rng;
 use std::marker::PhantomData;
@@ -31,8 +32,8 @@ impl TestPtx for AddF32 {
             .replace("<SAT>", if self.sat { ".sat" } else { "" })
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -158,10 +159,15 @@ pub fn all_tests() -> Vec<TestCase> {
                     if ftz { "_ftz" } else { "" },
                     if sat { "_sat" } else { "" }
                 );
-                tests.push(TestCase::new(
-                    name,
-                    make_random(AddF32::new(rounding, ftz, sat)),
-                ));
+                let metadata = TestMetadata::new("add")
+                    .types(["f32"])
+                    .modifiers([rounding.as_str()])
+                    .flag("ftz", ftz)
+                    .flag("sat", sat);
+                tests.push(
+                    TestCase::new(name, make_random(AddF32::new(rounding, ftz, sat)))
+                        .with_metadata(metadata),
+                );
             }
         }
     }
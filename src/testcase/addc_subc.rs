@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestCase, TestCommon, TestPtx};
 
 static ADDC_SUBC_PTX: &str = include_str!("addc_subc.ptx");
@@ -11,7 +12,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: false,
                 is_sub: false,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("addc").types(["u32"])),
         TestCase::new(
             "addc_cc_u32".to_string(),
             make_random(AddcOrSubc {
@@ -19,7 +21,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: true,
                 is_sub: false,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("addc").types(["u32"]).modifiers(["cc"])),
         TestCase::new(
             "subc_u32".to_string(),
             make_random(AddcOrSubc {
@@ -27,7 +30,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: false,
                 is_sub: true,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("subc").types(["u32"])),
         TestCase::new(
             "subc_cc_u32".to_string(),
             make_random(AddcOrSubc {
@@ -35,7 +39,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: true,
                 is_sub: true,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("subc").types(["u32"]).modifiers(["cc"])),
         TestCase::new(
             "addc_s32".to_string(),
             make_random(AddcOrSubc {
@@ -43,7 +48,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: false,
                 is_sub: false,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("addc").types(["s32"])),
         TestCase::new(
             "addc_cc_s32".to_string(),
             make_random(AddcOrSubc {
@@ -51,7 +57,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: true,
                 is_sub: false,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("addc").types(["s32"]).modifiers(["cc"])),
         TestCase::new(
             "subc_s32".to_string(),
             make_random(AddcOrSubc {
@@ -59,7 +66,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: false,
                 is_sub: true,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("subc").types(["s32"])),
         TestCase::new(
             "subc_cc_s32".to_string(),
             make_random(AddcOrSubc {
@@ -67,7 +75,8 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: true,
                 is_sub: true,
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("subc").types(["s32"]).modifiers(["cc"])),
     ]
 }
 
@@ -85,8 +94,8 @@ impl TestPtx for AddcOrSubc {
             .replace("<CC>", if self.carry_out { ".cc" } else { "" })
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "output"]
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, PtxScalar, RandomTest, RangeTest, TestCase, TestCommon, TestPtx};
 use num::cast::AsPrimitive;
 use num::PrimInt;
@@ -23,6 +24,7 @@ where
 {
     let test = make_random(Bfe::<T>::default());
     TestCase::new(format!("bfe_rng_{}", T::name()), test)
+        .with_metadata(TestMetadata::new("bfe").types([T::name()]))
 }
 
 #[derive(Default)]
@@ -37,8 +39,8 @@ impl<T: PtxScalar> TestPtx for Bfe<T> {
             .replace("<TYPE_SIZE>", &mem::size_of::<T>().to_string())
     }
 
-    fn args(&self) -> &[&str] {
-        &[
+    fn args(&self) -> Vec<&str> {
+        vec![
             "input",
             "positions",
             "lengths",
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, PtxScalar, RandomTest, TestCase, TestCommon, TestPtx};
 use num::{cast::AsPrimitive, PrimInt};
 use rand::{distributions::Standard, prelude::Distribution, Rng};
@@ -18,7 +19,9 @@ where
 {
     let bits = mem::size_of::<T>() * 8;
     let test = make_random(Bfi::<T>::default());
-    TestCase::new(format!("bfi_rng_b{}", bits), test)
+    let type_ = format!("b{}", bits);
+    TestCase::new(format!("bfi_rng_{}", type_), test)
+        .with_metadata(TestMetadata::new("bfi").types([type_.as_str()]))
 }
 
 #[derive(Default)]
@@ -35,8 +38,8 @@ impl<T: PtxScalar> TestPtx for Bfi<T> {
             .replace("<TYPE_SIZE>", &mem::size_of::<T>().to_string())
     }
 
-    fn args(&self) -> &[&str] {
-        &[
+    fn args(&self) -> Vec<&str> {
+        vec![
             "input_a",
             "input_b",
             "positions",
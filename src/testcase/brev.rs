@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{self, make_range, PtxScalar, TestCase, TestCommon, TestPtx};
 use num::PrimInt;
 use rand::{distributions::Standard, prelude::Distribution};
@@ -17,7 +18,9 @@ where
 {
     let bits = mem::size_of::<u32>() * 8;
     let test = make_range(Brev::<u32>::new());
-    TestCase::new(format!("brev_b{}", bits), test)
+    let type_ = format!("b{}", bits);
+    TestCase::new(format!("brev_{}", type_), test)
+        .with_metadata(TestMetadata::new("brev").types([type_.as_str()]))
 }
 
 pub struct Brev<T: PtxScalar> {
@@ -40,8 +43,8 @@ impl<T: PtxScalar> TestPtx for Brev<T> {
             .replace("<TYPE_SIZE>", &mem::size_of::<T>().to_string())
     }
 
-    fn args(&self) -> &[&str] {
-        &[
+    fn args(&self) -> Vec<&str> {
+        vec![
             "input",
             "output",
         ]
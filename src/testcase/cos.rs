@@ -1,4 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 use core::f32;
 
@@ -14,8 +15,12 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn cos(ftz: bool) -> TestCase {
     let test = make_range(Cos { ftz });
+    let metadata = TestMetadata::new("cos")
+        .types(["f32"])
+        .modifiers(["approx"])
+        .flag("ftz", ftz);
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("cos_approx{}", ftz), test)
+    TestCase::new(format!("cos_approx{}", ftz), test).with_metadata(metadata)
 }
 
 struct Cos {
@@ -28,8 +33,8 @@ impl TestPtx for Cos {
         PTX.replace("<FTZ>", &ftz)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
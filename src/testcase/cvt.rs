@@ -1,4 +1,5 @@
 use crate::common::{llvm_get_rounding, llvm_set_rounding};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, TestFunction, TestPtx};
 use crate::{
     common::Rounding,
@@ -118,7 +119,10 @@ macro_rules! gen_test {
                 if is_invalid_cvt::<$output, $input>($rnd.as_ptx(), $ftz, $sat) {
                     $invalid.push((name, test));
                 } else {
-                    $vec.push(test::TestCase::new(name, test));
+                    $vec.push(
+                        test::TestCase::new(name, test)
+                            .with_metadata(metadata::<$output, $input>($rnd, $ftz, $sat)),
+                    );
                 }
             }
         )*
@@ -163,8 +167,8 @@ impl<To: PtxScalar, From: PtxScalar> TestPtx for Cvt<To, From> {
             .replace("<MODIFIERS>", &modifiers)
     }
 
-    fn args(&self) -> &[&str] {
-        &[
+    fn args(&self) -> Vec<&str> {
+        vec![
             "input",
             "output",
         ]
@@ -227,14 +231,26 @@ fn test_case<To: PtxScalar, From: PtxScalar + HostConvert<To>>(
     (name, test)
 }
 
+fn metadata<To: PtxScalar, From: PtxScalar>(
+    rnd: Rounding,
+    ftz: bool,
+    sat: bool,
+) -> TestMetadata {
+    TestMetadata::new("cvt")
+        .types([To::name(), From::name()])
+        .modifiers([rnd.as_str()])
+        .flag("ftz", ftz)
+        .flag("sat", sat)
+}
+
 pub fn all_tests() -> Vec<TestCase> {
     let mut result = Vec::new();
     let mut invalid_tests = Vec::new();
     gen_test!(result, invalid_tests);
-    result.push(TestCase::join_invalid_tests(
-        "cvt_invalid".to_string(),
-        invalid_tests,
-    ));
+    result.push(
+        TestCase::join_invalid_tests("cvt_invalid".to_string(), invalid_tests)
+            .with_metadata(TestMetadata::new("cvt")),
+    );
     result
 }
 
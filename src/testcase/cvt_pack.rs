@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestPtx};
 use crate::test::{PtxScalar, TestCase, TestCommon};
 use std::marker::PhantomData;
@@ -9,10 +10,24 @@ pub fn all_tests() -> Vec<TestCase> {
         TestCase::new(
             "cvt_pack_sat_u8_s32_b32".to_string(),
             make_random(CvtPack::<u8>::default()),
+        )
+        .with_metadata(
+            TestMetadata::new("cvt")
+                .types(["u8", "s32", "b32"])
+                .modifiers(["pack", "sat"])
+                .min_sm(72)
+                .min_ptx(6, 5),
         ),
         TestCase::new(
             "cvt_pack_sat_s8_s32_b32".to_string(),
             make_random(CvtPack::<i8>::default()),
+        )
+        .with_metadata(
+            TestMetadata::new("cvt")
+                .types(["s8", "s32", "b32"])
+                .modifiers(["pack", "sat"])
+                .min_sm(72)
+                .min_ptx(6, 5),
         ),
     ]
 }
@@ -23,8 +38,8 @@ struct CvtPack<T> {
 }
 
 impl<T: PtxScalar> TestPtx for CvtPack<T> {
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "output"]
     }
 
     fn body(&self) -> String {
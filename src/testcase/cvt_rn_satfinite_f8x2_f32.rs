@@ -1,5 +1,6 @@
 use float8::{F8E4M3, F8E5M2};
 
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, Fp8, RangeTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("cvt_rn_satfinite_f8x2_f32.ptx");
@@ -9,10 +10,24 @@ pub fn all_tests() -> Vec<TestCase> {
         TestCase::new(
             "cvt_rn_satfinite_e4m3x2_f32".to_string(),
             make_range(Cvt::<F8E4M3>::new()),
+        )
+        .with_metadata(
+            TestMetadata::new("cvt")
+                .types(["e4m3x2", "f32"])
+                .modifiers(["rn", "satfinite"])
+                .min_sm(89)
+                .min_ptx(7, 8),
         ),
         TestCase::new(
             "cvt_rn_satfinite_e5m2x2_f32".to_string(),
             make_range(Cvt::<F8E5M2>::new()),
+        )
+        .with_metadata(
+            TestMetadata::new("cvt")
+                .types(["e5m2x2", "f32"])
+                .modifiers(["rn", "satfinite"])
+                .min_sm(89)
+                .min_ptx(7, 8),
         ),
     ]
 }
@@ -35,8 +50,8 @@ impl<ToElem: Fp8> TestPtx for Cvt<ToElem> {
         PTX.replace("<OUTPUT>", &t)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
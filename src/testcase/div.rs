@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::{
     common,
     test::{make_random, RandomTest, TestCase, TestCommon, TestPtx},
@@ -52,8 +53,8 @@ impl TestPtx for DivF32 {
             .replace("<FLUSH>", if self.ftz { ".ftz" } else { "" })
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -204,7 +205,13 @@ pub fn all_tests() -> Vec<TestCase> {
                 DivVariant::Rnd(ref r) => format!("{}", r.as_str()),
             };
             let name = format!("div_{}{}_f32", variant_name, if ftz { "_ftz" } else { "" });
-            tests.push(TestCase::new(name, make_random(DivF32::new(variant, ftz))));
+            let metadata = TestMetadata::new("div")
+                .types(["f32"])
+                .modifiers([variant_name.as_str()])
+                .flag("ftz", ftz);
+            tests.push(
+                TestCase::new(name, make_random(DivF32::new(variant, ftz))).with_metadata(metadata),
+            );
         }
     }
     tests
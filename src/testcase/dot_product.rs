@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestCase, TestCommon, TestPtx};
 use rand::Rng;
 
@@ -12,15 +13,22 @@ pub fn all_tests() -> Vec<TestCase> {
             let atype = if a_signed { "s32" } else { "u32" };
             let btype = if b_signed { "s32" } else { "u32" };
             let name = format!("dp4a_{atype}_{btype}");
-            tests.push(TestCase::new(
-                name,
-                make_random(DotProd {
-                    dp2a: false,
-                    a_signed,
-                    b_signed,
-                    hi: false,
-                }),
-            ));
+            let metadata = TestMetadata::new("dp4a")
+                .types([atype, btype])
+                .min_sm(61)
+                .min_ptx(5, 0);
+            tests.push(
+                TestCase::new(
+                    name,
+                    make_random(DotProd {
+                        dp2a: false,
+                        a_signed,
+                        b_signed,
+                        hi: false,
+                    }),
+                )
+                .with_metadata(metadata),
+            );
         }
     }
 
@@ -32,15 +40,23 @@ pub fn all_tests() -> Vec<TestCase> {
                 let atype = if a_signed { "s32" } else { "u32" };
                 let btype = if b_signed { "s32" } else { "u32" };
                 let name = format!("dp2a_{mode}_{atype}_{btype}",);
-                tests.push(TestCase::new(
-                    name,
-                    make_random(DotProd {
-                        dp2a: true,
-                        a_signed,
-                        b_signed,
-                        hi,
-                    }),
-                ));
+                let metadata = TestMetadata::new("dp2a")
+                    .types([atype, btype])
+                    .modifiers([mode])
+                    .min_sm(61)
+                    .min_ptx(5, 0);
+                tests.push(
+                    TestCase::new(
+                        name,
+                        make_random(DotProd {
+                            dp2a: true,
+                            a_signed,
+                            b_signed,
+                            hi,
+                        }),
+                    )
+                    .with_metadata(metadata),
+                );
             }
         }
     }
@@ -68,8 +84,8 @@ impl TestPtx for DotProd {
         PTX.replace("<DPINST>", &instr)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input0", "input1", "input2", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input0", "input1", "input2", "output"]
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::common;
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 use core::f32;
 
@@ -6,8 +7,16 @@ pub static PTX: &str = include_str!("ex2.ptx");
 
 pub fn all_tests() -> Vec<TestCase> {
     vec![
-        TestCase::new("ex2_approx".to_string(), make_range(Ex2::new(false))),
-        TestCase::new("ex2_approx_ftz".to_string(), make_range(Ex2::new(true))),
+        TestCase::new("ex2_approx".to_string(), make_range(Ex2::new(false))).with_metadata(
+            TestMetadata::new("ex2")
+                .types(["f32"])
+                .modifiers(["approx"]),
+        ),
+        TestCase::new("ex2_approx_ftz".to_string(), make_range(Ex2::new(true))).with_metadata(
+            TestMetadata::new("ex2")
+                .types(["f32"])
+                .modifiers(["approx", "ftz"]),
+        ),
     ]
 }
 
@@ -26,8 +35,8 @@ impl TestPtx for Ex2 {
         PTX.replace("<FLUSH>", if self.ftz { ".ftz" } else { "" })
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
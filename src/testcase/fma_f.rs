@@ -1,4 +1,5 @@
 use crate::common;
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestCase, TestCommon, TestPtx};
 use rand::Rng;
 use std::marker::PhantomData;
@@ -98,8 +99,8 @@ impl TestPtx for FmaF32 {
             .replace("<SAT>", if self.sat { ".sat" } else { "" })
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "output"]
     }
 }
 
@@ -173,10 +174,15 @@ pub fn all_tests() -> Vec<TestCase> {
                     if ftz { "_ftz" } else { "" },
                     if sat { "_sat" } else { "" }
                 );
-                tests.push(TestCase::new(
-                    name,
-                    make_random(FmaF32::new(rounding, ftz, sat)),
-                ));
+                let metadata = TestMetadata::new("fma")
+                    .types(["f32"])
+                    .modifiers([rounding.as_str()])
+                    .flag("ftz", ftz)
+                    .flag("sat", sat);
+                tests.push(
+                    TestCase::new(name, make_random(FmaF32::new(rounding, ftz, sat)))
+                        .with_metadata(metadata),
+                );
             }
         }
     }
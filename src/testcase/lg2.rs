@@ -1,4 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 use core::f32;
 
@@ -10,8 +11,12 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn lg2(ftz: bool) -> TestCase {
     let test = make_range(Lg2 { ftz });
+    let metadata = TestMetadata::new("lg2")
+        .types(["f32"])
+        .modifiers(["approx"])
+        .flag("ftz", ftz);
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("lg2_approx{}", ftz), test)
+    TestCase::new(format!("lg2_approx{}", ftz), test).with_metadata(metadata)
 }
 
 struct Lg2 {
@@ -24,8 +29,8 @@ impl TestPtx for Lg2 {
         PTX.replace("<FTZ>", &ftz)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::common::WideningMul;
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, PtxScalar, RandomTest, TestCase, TestCommon, TestPtx};
 use num::cast::AsPrimitive;
 use num::PrimInt;
@@ -65,30 +66,30 @@ where
 pub fn all_tests() -> Vec<TestCase> {
     let mut tests = Vec::new();
     for mode in [Mode::Low, Mode::High] {
-        tests.push(TestCase::new(
-            format!("mad_{}_u16", mode.ptx_modifier()),
-            make_random(MadTest::<u16, u16>::new(mode, false)),
-        ));
-        tests.push(TestCase::new(
-            format!("mad_{}_s16", mode.ptx_modifier()),
-            make_random(MadTest::<i16, i16>::new(mode, false)),
-        ));
+        tests.push(mad::<u16, u16>(mode, false));
+        tests.push(mad::<i16, i16>(mode, false));
     }
-    tests.push(TestCase::new(
-        "mad_wide_u16".to_string(),
-        make_random(MadTest::<u16, u32>::new(Mode::Wide, false)),
-    ));
-    tests.push(TestCase::new(
-        "mad_wide_s16".to_string(),
-        make_random(MadTest::<i16, i32>::new(Mode::Wide, false)),
-    ));
-    tests.push(TestCase::new(
-        "mad_hi_sat_s32".to_string(),
-        make_random(MadTest::<i32, i32>::new(Mode::High, true)),
-    ));
+    tests.push(mad::<u16, u32>(Mode::Wide, false));
+    tests.push(mad::<i16, i32>(Mode::Wide, false));
+    tests.push(mad::<i32, i32>(Mode::High, true));
     tests
 }
 
+fn mad<T, U>(mode: Mode, sat: bool) -> TestCase
+where
+    MadTest<T, U>: RandomTest + Send + 'static,
+    T: PtxScalar,
+    U: PtxScalar,
+{
+    let sat_txt = if sat { "_sat" } else { "" };
+    let name = format!("mad_{}{sat_txt}_{}", mode.ptx_modifier(), T::name());
+    let metadata = TestMetadata::new("mad")
+        .types([T::name()])
+        .modifiers([mode.ptx_modifier()])
+        .flag("sat", sat);
+    TestCase::new(name, make_random(MadTest::<T, U>::new(mode, sat))).with_metadata(metadata)
+}
+
 struct MadTest<T: PtxScalar, U: PtxScalar> {
     mode: Mode,
     saturate: bool,
@@ -116,8 +117,8 @@ impl<T: PtxScalar, U: PtxScalar> TestPtx for MadTest<T, U> {
             .replace("<SAT>", sat)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "output"]
     }
 }
 
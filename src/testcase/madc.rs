@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestCase, TestCommon, TestPtx};
 
 static PTX: &str = include_str!("madc.ptx");
@@ -10,13 +11,19 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: false,
                 type_: "u32",
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("madc").types(["u32"]).modifiers(["lo"])),
         TestCase::new(
             "madc_cc_u32".to_string(),
             make_random(Madc {
                 carry_out: true,
                 type_: "u32",
             }),
+        )
+        .with_metadata(
+            TestMetadata::new("madc")
+                .types(["u32"])
+                .modifiers(["lo", "cc"]),
         ),
         TestCase::new(
             "madc_s32".to_string(),
@@ -24,13 +31,19 @@ pub fn all_tests() -> Vec<TestCase> {
                 carry_out: false,
                 type_: "s32",
             }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("madc").types(["s32"]).modifiers(["lo"])),
         TestCase::new(
             "madc_cc_s32".to_string(),
             make_random(Madc {
                 carry_out: true,
                 type_: "s32",
             }),
+        )
+        .with_metadata(
+            TestMetadata::new("madc")
+                .types(["s32"])
+                .modifiers(["lo", "cc"]),
         ),
     ]
 }
@@ -46,8 +59,8 @@ impl TestPtx for Madc {
             .replace("<CC>", if self.carry_out { ".cc" } else { "" })
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "input_d", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "input_d", "output"]
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::{
     common,
     test::{make_range, RangeTest, TestCase, TestCommon, TestPtx},
@@ -23,7 +24,13 @@ fn min(ftz: bool, nan: bool) -> TestCase {
         if ftz { "_ftz" } else { "" },
         if nan { "_nan" } else { "" }
     );
-    TestCase::new(name.to_string(), make_range(Min { ftz, nan }))
+    let metadata = TestMetadata::new("min")
+        .types(["f16"])
+        .flag("ftz", ftz)
+        .flag("NaN", nan)
+        .min_sm(80)
+        .min_ptx(7, 0);
+    TestCase::new(name.to_string(), make_range(Min { ftz, nan })).with_metadata(metadata)
 }
 
 fn max(ftz: bool, nan: bool) -> TestCase {
@@ -32,7 +39,13 @@ fn max(ftz: bool, nan: bool) -> TestCase {
         if ftz { "_ftz" } else { "" },
         if nan { "_nan" } else { "" }
     );
-    TestCase::new(name.to_string(), make_range(Max { ftz, nan }))
+    let metadata = TestMetadata::new("max")
+        .types(["f16"])
+        .flag("ftz", ftz)
+        .flag("NaN", nan)
+        .min_sm(80)
+        .min_ptx(7, 0);
+    TestCase::new(name.to_string(), make_range(Max { ftz, nan })).with_metadata(metadata)
 }
 
 struct Min {
@@ -53,8 +66,8 @@ impl TestPtx for Min {
             .replace("<OP>", &name)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -97,8 +110,8 @@ impl TestPtx for Max {
             .replace("<OP>", &name)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -5,7 +5,8 @@ use std::{
 };
 
 use crate::{
-    cuda::Cuda,
+    driver::Driver,
+    metadata::{PtxVersion, TestMetadata},
     nvrtc::Nvrtc,
     test::{TestCase, TestPtx},
 };
@@ -50,184 +51,273 @@ mod testp;
 mod vshr;
 
 pub trait TestContext {
-    fn cuda(&self) -> &Cuda;
+    fn driver(&self) -> &dyn Driver;
     fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String>;
+    /// Text that golden outputs of the test are keyed by, see `golden_source`
+    fn golden_source(&self, ptx: &dyn TestPtx) -> String;
 }
 
 pub struct TestFixture<L> {
     pub libs: L,
+    pub target: Target,
 }
 
-const PTX_HEADER: &'_ str = "
-    .version 7.8
-    .target sm_90
-    .address_size 64
-";
+/// Header of the generated PTX modules and how NVRTC compiles the CUDA sources.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub ptx_version: PtxVersion,
+    /// Value of `.target`, e.g. `sm_90a`
+    pub sm: String,
+    /// Value of NVRTC `-arch`, e.g. `sm_90` or `compute_90`
+    pub nvrtc_arch: String,
+    /// Passed to NVRTC after `-arch`
+    pub nvrtc_options: Vec<String>,
+}
 
-impl TestContext for TestFixture<(Cuda,)> {
-    fn cuda(&self) -> &Cuda {
-        &self.libs.0
+impl Target {
+    /// Why a test described by `metadata` can't be compiled for this target, `None` if it can.
+    /// NVRTC picks the PTX version on its own, so with `nvrtc` only the architecture is checked.
+    pub fn unsupported(&self, metadata: &TestMetadata, nvrtc: bool) -> Option<String> {
+        let (arch, ptx_version) = if nvrtc {
+            (&self.nvrtc_arch, None)
+        } else {
+            (&self.sm, Some(self.ptx_version))
+        };
+        if arch_number(arch).unwrap() < metadata.min_sm {
+            return Some(format!("requires sm_{}, target is {arch}", metadata.min_sm));
+        }
+        match ptx_version {
+            Some(version) if version < metadata.min_ptx => Some(format!(
+                "requires PTX {}, target is PTX {version}",
+                metadata.min_ptx
+            )),
+            _ => None,
+        }
     }
+}
 
-    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String> {
-        /// Generate PTX test function signature.
-        fn fmt_ptx_signature(args: &[&str]) -> String {
-            let args: Vec<_> = args.iter().map(|a| format!(".param .u64 {}", a)).collect();
-            format!(".entry run({})", args.join(", "))
-        }
+/// Number of an architecture like `sm_90a` or `compute_75`, `None` if it's not one.
+pub fn arch_number(arch: &str) -> Option<u32> {
+    arch.strip_prefix("sm_")
+        .or_else(|| arch.strip_prefix("compute_"))?
+        .trim_end_matches(['a', 'f'])
+        .parse()
+        .ok()
+}
 
-        /// Generate PTX to load values of test function parameters.
-        fn fmt_ptx_params_load(args: &[&str]) -> String {
-            let mut text = String::new();
-            for arg in args {
-                text.push_str(&format!(".reg .u64    {name}_addr;\n", name = arg));
-                text.push_str(&format!(
-                    "ld.param.u64 {name}_addr, [{name}];\n",
-                    name = arg
-                ));
-            }
-            text
-        }
+/// Full PTX module of a test, as loaded by `TestFixture<(Box<dyn Driver>,)>`.
+pub fn ptx_source(ptx: &dyn TestPtx, target: &Target) -> String {
+    /// Generate PTX test function signature.
+    fn fmt_ptx_signature(args: &[&str]) -> String {
+        let args: Vec<_> = args.iter().map(|a| format!(".param .u64 {}", a)).collect();
+        format!(".entry run({})", args.join(", "))
+    }
 
-        Ok(CString::new(format!(
-            "{}\n{}\n{{\n{}\n{}\nret;\n}}",
-            PTX_HEADER,
-            fmt_ptx_signature(ptx.args()),
-            fmt_ptx_params_load(ptx.args()),
-            ptx.body(),
-        ))
-        .unwrap())
+    /// Generate PTX to load values of test function parameters.
+    fn fmt_ptx_params_load(args: &[&str]) -> String {
+        let mut text = String::new();
+        for arg in args {
+            text.push_str(&format!(".reg .u64    {name}_addr;\n", name = arg));
+            text.push_str(&format!(
+                "ld.param.u64 {name}_addr, [{name}];\n",
+                name = arg
+            ));
+        }
+        text
     }
+
+    // Tests compute their addresses with 64-bit arithmetic, so the address size is fixed
+    format!(
+        "\n    .version {}\n    .target {}\n    .address_size 64\n\n{}\n{{\n{}\n{}\nret;\n}}",
+        target.ptx_version,
+        target.sm,
+        fmt_ptx_signature(&ptx.args()),
+        fmt_ptx_params_load(&ptx.args()),
+        ptx.body(),
+    )
 }
 
-impl TestContext for TestFixture<(Cuda, Nvrtc)> {
-    fn cuda(&self) -> &Cuda {
-        &self.libs.0
+/// CUDA C++ source of a test with its PTX as inline `asm`, as compiled by
+/// `TestFixture<(Box<dyn Driver>, Nvrtc)>`.
+pub fn cuda_source(ptx: &dyn TestPtx) -> String {
+    /// Generate CUDA test function signature.
+    fn fmt_cuda_signature(args: &[&str]) -> String {
+        let args: Vec<_> = args
+            .iter()
+            .map(|a| format!("unsigned long long * {}", a))
+            .collect();
+        format!("extern \"C\" __global__ void run({})", args.join(", "))
     }
 
-    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String> {
-        /// Generate CUDA test function signature.
-        fn fmt_cuda_signature(args: &[&str]) -> String {
-            let args: Vec<_> = args
-                .iter()
-                .map(|a| format!("unsigned long long * {}", a))
-                .collect();
-            format!("extern \"C\" __global__ void run({})", args.join(", "))
+    /// Generate PTX to load values of test function parameters.
+    fn fmt_cuda_inline_ptx_params_load(args: &[&str]) -> String {
+        let mut text = String::new();
+        for (arg_index, arg_name) in args.iter().enumerate() {
+            text.push_str(&format!(".reg .u64 {name}_addr;\n", name = arg_name));
+            text.push_str(&format!(
+                "mov.u64   {name}_addr, %{index};\n",
+                name = arg_name,
+                index = arg_index
+            ));
         }
+        text
+    }
 
-        /// Generate PTX to load values of test function parameters.
-        fn fmt_cuda_inline_ptx_params_load(args: &[&str]) -> String {
-            let mut text = String::new();
-            for (arg_index, arg_name) in args.iter().enumerate() {
-                text.push_str(&format!(".reg .u64 {name}_addr;\n", name = arg_name));
-                text.push_str(&format!(
-                    "mov.u64   {name}_addr, %{index};\n",
-                    name = arg_name,
-                    index = arg_index
-                ));
-            }
-            text
-        }
+    /// Generate CUDA parameter list for inline PTX.
+    fn fmt_cuda_inline_ptx_params(args: &[&str]) -> String {
+        args.iter()
+            .map(|a| format!(r#""l"({})"#, a))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 
-        /// Generate CUDA parameter list for inline PTX.
-        fn fmt_cuda_inline_ptx_params(args: &[&str]) -> String {
-            args.iter()
-                .map(|a| format!(r#""l"({})"#, a))
-                .collect::<Vec<_>>()
-                .join(", ")
-        }
+    /// Transform raw PTX into CUDA inline PTX function body.
+    fn ptx_to_inline(args: &[&str], body: &str) -> String {
+        let mut body = body.to_string();
 
-        /// Transform raw PTX into CUDA inline PTX function body.
-        fn ptx_to_inline(args: &[&str], body: &str) -> String {
-            let mut body = body.to_string();
+        // Escape "%" (used for things like %tid (thread id) etc.)
+        body = body.replace("%", "%%");
 
-            // Escape "%" (used for things like %tid (thread id) etc.)
-            body = body.replace("%", "%%");
+        body = format!("{}\n{}", fmt_cuda_inline_ptx_params_load(args), body,);
 
-            body = format!("{}\n{}", fmt_cuda_inline_ptx_params_load(args), body,);
+        body = body
+            .lines()
+            .map(|l| format!("\"{}\"\n", l))
+            .collect::<Vec<_>>()
+            .join("    ");
 
-            body = body
-                .lines()
-                .map(|l| format!("\"{}\"\n", l))
-                .collect::<Vec<_>>()
-                .join("    ");
+        format!("asm({}    :: {});", body, fmt_cuda_inline_ptx_params(args),)
+    }
 
-            format!("asm({}    :: {});", body, fmt_cuda_inline_ptx_params(args),)
-        }
+    format!(
+        "{} {{\n{}\n}}",
+        fmt_cuda_signature(&ptx.args()),
+        ptx_to_inline(&ptx.args(), &ptx.body()),
+    )
+}
 
-        let nvrtc = &self.libs.1;
-
-        let source_cuda = format!(
-            "{} {{\n{}\n}}",
-            fmt_cuda_signature(ptx.args()),
-            ptx_to_inline(ptx.args(), &ptx.body()),
-        );
-        let source_cuda_c = CString::new(source_cuda.clone()).unwrap();
-
-        let mut program = ptr::null_mut();
-        unsafe {
-            nvrtc.nvrtcCreateProgram(
-                &mut program,
-                source_cuda_c.as_ptr() as _,
-                ptr::null() as _,
-                0,
-                ptr::null(),
-                ptr::null(),
-            )
-        }
-        .unwrap();
+impl TestContext for TestFixture<(Box<dyn Driver>,)> {
+    fn driver(&self) -> &dyn Driver {
+        &*self.libs.0
+    }
 
-        let options = ["-arch=sm_90"].map(|opt| CString::new(opt).unwrap());
-        let options_c: Vec<_> = options.iter().map(|opt| opt.as_c_str().as_ptr()).collect();
+    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String> {
+        Ok(CString::new(ptx_source(ptx, &self.target)).unwrap())
+    }
 
-        let result = unsafe {
-            nvrtc.nvrtcCompileProgram(program, options_c.len() as _, options_c.as_ptr() as _)
-        };
+    fn golden_source(&self, ptx: &dyn TestPtx) -> String {
+        golden_source(ptx, &self.target, false)
+    }
+}
 
-        if result.is_err() {
-            let error = unsafe { CStr::from_ptr(nvrtc.nvrtcGetErrorString(result)) };
-            let error = String::from_utf8_lossy(error.to_bytes()).to_string();
+impl TestContext for TestFixture<(Box<dyn Driver>, Nvrtc)> {
+    fn driver(&self) -> &dyn Driver {
+        &*self.libs.0
+    }
 
-            let mut log_size = 0;
-            unsafe { nvrtc.nvrtcGetProgramLogSize(program, &mut log_size) }.unwrap();
+    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String> {
+        compile_cuda(&self.libs.1, &cuda_source(ptx), &self.target)
+    }
 
-            let log_layout = Layout::array::<core::ffi::c_char>(log_size).unwrap();
-            let log_buffer = unsafe { alloc(log_layout) };
+    fn golden_source(&self, ptx: &dyn TestPtx) -> String {
+        golden_source(ptx, &self.target, true)
+    }
+}
 
-            unsafe { nvrtc.nvrtcGetProgramLog(program, log_buffer as _) }.unwrap();
+/// Everything that decides what gets loaded for a test: the PTX module, or with `nvrtc` the CUDA
+/// source followed by the NVRTC options. Golden outputs are keyed by its hash, so that outputs
+/// recorded for another target or compilation mode are rejected.
+pub fn golden_source(ptx: &dyn TestPtx, target: &Target, nvrtc: bool) -> String {
+    if !nvrtc {
+        return ptx_source(ptx, target);
+    }
+    let mut source = cuda_source(ptx);
+    for option in nvrtc_options(target) {
+        source.push('\n');
+        source.push_str(&option);
+    }
+    source
+}
 
-            let log_cstr = unsafe { CStr::from_ptr(log_buffer as _) };
-            let log = String::from_utf8_lossy(log_cstr.to_bytes()).to_string();
+/// Options passed to NVRTC when compiling for `target`.
+fn nvrtc_options(target: &Target) -> Vec<String> {
+    [format!("-arch={}", target.nvrtc_arch)]
+        .into_iter()
+        .chain(target.nvrtc_options.iter().cloned())
+        .collect()
+}
 
-            unsafe { dealloc(log_buffer, log_layout) };
+/// Compile CUDA source to PTX with NVRTC options of `target`, on failure the error contains the
+/// compilation log.
+pub fn compile_cuda(nvrtc: &Nvrtc, source_cuda: &str, target: &Target) -> Result<CString, String> {
+    let source_cuda_c = CString::new(source_cuda).unwrap();
+
+    let mut program = ptr::null_mut();
+    unsafe {
+        nvrtc.nvrtcCreateProgram(
+            &mut program,
+            source_cuda_c.as_ptr() as _,
+            ptr::null() as _,
+            0,
+            ptr::null(),
+            ptr::null(),
+        )
+    }
+    .unwrap();
 
-            let error = format!("NVRTC error: {error}");
-            let log = format!("Compilation produced the following log:\n{log}");
-            let mut source = format!("Program source:\n");
-            for (i, line) in source_cuda.lines().enumerate() {
-                source.push_str(&format!("{:3} | {}\n", i + 1, line));
-            }
+    let options: Vec<_> = nvrtc_options(target)
+        .into_iter()
+        .map(|opt| CString::new(opt).unwrap())
+        .collect();
+    let options_c: Vec<_> = options.iter().map(|opt| opt.as_c_str().as_ptr()).collect();
 
-            return Err(format!("{error}\n{log}\n{source}"));
-        }
+    let result = unsafe {
+        nvrtc.nvrtcCompileProgram(program, options_c.len() as _, options_c.as_ptr() as _)
+    };
+
+    if result.is_err() {
+        let error = unsafe { CStr::from_ptr(nvrtc.nvrtcGetErrorString(result)) };
+        let error = String::from_utf8_lossy(error.to_bytes()).to_string();
 
-        let mut ptx_size = 0;
-        unsafe { nvrtc.nvrtcGetPTXSize(program, &mut ptx_size) }.unwrap();
+        let mut log_size = 0;
+        unsafe { nvrtc.nvrtcGetProgramLogSize(program, &mut log_size) }.unwrap();
 
-        let source_ptx_layout = Layout::array::<core::ffi::c_char>(ptx_size).unwrap();
-        let source_ptx_buffer = unsafe { alloc(source_ptx_layout) };
+        let log_layout = Layout::array::<core::ffi::c_char>(log_size).unwrap();
+        let log_buffer = unsafe { alloc(log_layout) };
 
-        unsafe { nvrtc.nvrtcGetPTX(program, source_ptx_buffer as _) }.unwrap();
+        unsafe { nvrtc.nvrtcGetProgramLog(program, log_buffer as _) }.unwrap();
 
-        let source_ptx = unsafe { CStr::from_ptr(source_ptx_buffer as _) };
-        let source_ptx = source_ptx.to_owned();
+        let log_cstr = unsafe { CStr::from_ptr(log_buffer as _) };
+        let log = String::from_utf8_lossy(log_cstr.to_bytes()).to_string();
 
-        unsafe { nvrtc.nvrtcDestroyProgram(&mut program) }.unwrap();
+        unsafe { dealloc(log_buffer, log_layout) };
 
-        unsafe { dealloc(source_ptx_buffer, source_ptx_layout) };
+        let error = format!("NVRTC error: {error}");
+        let log = format!("Compilation produced the following log:\n{log}");
+        let mut source = format!("Program source:\n");
+        for (i, line) in source_cuda.lines().enumerate() {
+            source.push_str(&format!("{:3} | {}\n", i + 1, line));
+        }
 
-        Ok(source_ptx)
+        return Err(format!("{error}\n{log}\n{source}"));
     }
+
+    let mut ptx_size = 0;
+    unsafe { nvrtc.nvrtcGetPTXSize(program, &mut ptx_size) }.unwrap();
+
+    let source_ptx_layout = Layout::array::<core::ffi::c_char>(ptx_size).unwrap();
+    let source_ptx_buffer = unsafe { alloc(source_ptx_layout) };
+
+    unsafe { nvrtc.nvrtcGetPTX(program, source_ptx_buffer as _) }.unwrap();
+
+    let source_ptx = unsafe { CStr::from_ptr(source_ptx_buffer as _) };
+    let source_ptx = source_ptx.to_owned();
+
+    unsafe { nvrtc.nvrtcDestroyProgram(&mut program) }.unwrap();
+
+    unsafe { dealloc(source_ptx_buffer, source_ptx_layout) };
+
+    Ok(source_ptx)
 }
 
 pub fn tests() -> Vec<TestCase> {
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, PtxScalar, RangeTest, TestCase, TestCommon, TestPtx};
 use num::cast::AsPrimitive;
 use num::PrimInt;
@@ -59,7 +60,11 @@ where
     U: PrimInt + AsPrimitive<T>,
 {
     let test = make_range(MulTest::<T, U>::new(mode));
-    TestCase::new(format!("mul_{}_{}", mode.to_ptx(), T::name()), test)
+    TestCase::new(format!("mul_{}_{}", mode.to_ptx(), T::name()), test).with_metadata(
+        TestMetadata::new("mul")
+            .types([T::name()])
+            .modifiers([mode.to_ptx()]),
+    )
 }
 
 pub struct MulTest<T: PtxScalar, U: PtxScalar> {
@@ -85,8 +90,8 @@ impl<T: PtxScalar, U: PtxScalar> TestPtx for MulTest<T, U> {
             .replace("<MODE>", self.mode.to_ptx())
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -120,7 +125,11 @@ where
     U: PrimInt + AsPrimitive<T>,
 {
     let test = make_range(MulWideTest::<T, U>::new());
-    TestCase::new(format!("mul_wide_{}", T::name()), test)
+    TestCase::new(format!("mul_wide_{}", T::name()), test).with_metadata(
+        TestMetadata::new("mul")
+            .types([T::name()])
+            .modifiers(["wide"]),
+    )
 }
 
 pub struct MulWideTest<T: PtxScalar, U: PtxScalar> {
@@ -144,8 +153,8 @@ impl<T: PtxScalar, U: PtxScalar> TestPtx for MulWideTest<T, U> {
             .replace("<MODE>", "wide")
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{RandomTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("mul24.ptx");
@@ -15,8 +16,8 @@ impl TestPtx for Mul24 {
         PTX.replace("<MODE>", mode).replace("<TYPE>", typ)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input0", "input1", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input0", "input1", "output"]
     }
 }
 
@@ -76,10 +77,13 @@ pub fn all_tests() -> Vec<TestCase> {
                 if hi { "hi" } else { "lo" },
                 if signed { "s32" } else { "u32" },
             );
-            tests.push(TestCase::new(
-                name,
-                crate::test::make_random(Mul24 { signed, hi }),
-            ));
+            let metadata = TestMetadata::new("mul24")
+                .types([if signed { "s32" } else { "u32" }])
+                .modifiers([if hi { "hi" } else { "lo" }]);
+            tests.push(
+                TestCase::new(name, crate::test::make_random(Mul24 { signed, hi }))
+                    .with_metadata(metadata),
+            );
         }
     }
     tests
@@ -1,12 +1,15 @@
 use crate::common::flush_to_zero_f32;
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("neg.ptx");
 
 pub fn all_tests() -> Vec<TestCase> {
     vec![
-        TestCase::new("neg".to_string(), make_range(Neg { ftz: false })),
-        TestCase::new("neg_ftz".to_string(), make_range(Neg { ftz: true })),
+        TestCase::new("neg".to_string(), make_range(Neg { ftz: false }))
+            .with_metadata(TestMetadata::new("neg").types(["f32"])),
+        TestCase::new("neg_ftz".to_string(), make_range(Neg { ftz: true }))
+            .with_metadata(TestMetadata::new("neg").types(["f32"]).modifiers(["ftz"])),
     ]
 }
 
@@ -20,8 +23,8 @@ impl TestPtx for Neg {
         PTX.replace("<FTZ>", ftz)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
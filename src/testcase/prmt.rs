@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("prmt.ptx");
@@ -43,7 +44,10 @@ pub fn all_tests() -> Vec<TestCase> {
             PrmtMode::Generic => "prmt".to_string(),
             _ => format!("prmt_{}", mode.as_str().trim_start_matches('.')),
         };
-        tests.push(TestCase::new(name, make_range(Prmt { mode: *mode })));
+        let metadata = TestMetadata::new("prmt")
+            .types(["b32"])
+            .modifiers([mode.as_str()]);
+        tests.push(TestCase::new(name, make_range(Prmt { mode: *mode })).with_metadata(metadata));
     }
     tests
 }
@@ -57,8 +61,8 @@ impl TestPtx for Prmt {
         PTX.replace("<MODE>", self.mode.as_str())
     }
 
-    fn args(&self) -> &[&str] {
-        &["input0", "input1", "input2", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input0", "input1", "input2", "output"]
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::common::{self, flush_to_zero_f32, Rounding};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("rcp.ptx");
@@ -26,8 +27,12 @@ fn rcp_approx(ftz: bool) -> TestCase {
 fn rcp<const APPROX: bool>(rnd: Rounding, ftz: bool) -> TestCase {
     let test = make_range::<Rcp<APPROX>>(Rcp { rnd, ftz });
     let mode = if APPROX { "approx" } else { rnd.as_str() };
+    let metadata = TestMetadata::new("rcp")
+        .types(["f32"])
+        .modifiers([mode])
+        .flag("ftz", ftz);
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("rcp_{}{}", mode, ftz), test)
+    TestCase::new(format!("rcp_{}{}", mode, ftz), test).with_metadata(metadata)
 }
 
 pub struct Rcp<const APPROX: bool> {
@@ -42,8 +47,8 @@ impl<const APPROX: bool> TestPtx for Rcp<APPROX> {
         PTX.replace("<MODE>", &mode)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 
 static PTX: &str = include_str!("rsqrt.ptx");
@@ -13,8 +14,12 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn rsqrt_approx(ftz: bool) -> TestCase {
     let test = make_range(RsqrtApprox { ftz });
+    let metadata = TestMetadata::new("rsqrt")
+        .types(["f32"])
+        .modifiers(["approx"])
+        .flag("ftz", ftz);
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("rsqrt_approx{}", ftz), test)
+    TestCase::new(format!("rsqrt_approx{}", ftz), test).with_metadata(metadata)
 }
 
 struct RsqrtApprox {
@@ -30,8 +35,8 @@ impl TestPtx for RsqrtApprox {
         PTX.replace("<MODE>", &mode)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{RangeTest, TestCase, TestCommon, TestPtx};
 
 static PTX: &str = include_str!("sad.ptx");
@@ -13,8 +14,8 @@ impl TestPtx for Sad {
         PTX.replace("<TYPE>", typ)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input0", "input1", "input2", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input0", "input1", "input2", "output"]
     }
 }
 
@@ -84,10 +85,12 @@ pub fn all_tests() -> Vec<TestCase> {
         TestCase::new(
             "sad_u16".to_string(),
             crate::test::make_range(Sad { signed: false }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("sad").types(["u16"])),
         TestCase::new(
             "sad_s16".to_string(),
             crate::test::make_range(Sad { signed: true }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("sad").types(["s16"])),
     ]
 }
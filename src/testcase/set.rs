@@ -2,6 +2,7 @@ use num::cast::AsPrimitive;
 use num::traits::FromPrimitive;
 
 use crate::common::Comparison;
+use crate::metadata::TestMetadata;
 use crate::test::{
     make_random, make_range, PtxScalar, RandomTest, RangeTest, TestCase, TestCommon, TestPtx,
 };
@@ -53,8 +54,8 @@ where
         format_set::<T, U>(self.cmp_op, self.bool_input)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output_d"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output_d"]
     }
 }
 
@@ -177,8 +178,8 @@ impl TestPtx for SetFloatTest {
         format_set_bool::<f32>(self.cmp_op, self.bool_op, self.ftz)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "output_d"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "output_d"]
     }
 }
 
@@ -264,45 +265,73 @@ pub fn all_tests() -> Vec<TestCase> {
     let mut tests = Vec::new();
 
     for cmp in Comparison::iter_int() {
-        tests.push(TestCase::new(
-            format!("set_{}_u32_u16", cmp.as_str()),
-            make_range(SetIntTest::<u16, u32>::new(cmp, None)),
-        ));
+        tests.push(
+            TestCase::new(
+                format!("set_{}_u32_u16", cmp.as_str()),
+                make_range(SetIntTest::<u16, u32>::new(cmp, None)),
+            )
+            .with_metadata(
+                TestMetadata::new("set")
+                    .types(["u32", "u16"])
+                    .modifiers([cmp.as_str()]),
+            ),
+        );
         if cmp.signed() {
-            tests.push(TestCase::new(
-                format!("set_{}_u32_s16", cmp.as_str()),
-                make_range(SetIntTest::<i16, u32>::new(cmp, None)),
-            ));
+            tests.push(
+                TestCase::new(
+                    format!("set_{}_u32_s16", cmp.as_str()),
+                    make_range(SetIntTest::<i16, u32>::new(cmp, None)),
+                )
+                .with_metadata(
+                    TestMetadata::new("set")
+                        .types(["u32", "s16"])
+                        .modifiers([cmp.as_str()]),
+                ),
+            );
         }
         for bool_op in [BoolOp::And, BoolOp::Or, BoolOp::Xor] {
             for integer_predicate in [true, false] {
-                tests.push(TestCase::new(
-                    format!(
-                        "set_{}_{}_u32_u16_{}",
-                        cmp.as_str(),
-                        bool_op.as_str(),
-                        integer_predicate.to_string()
+                tests.push(
+                    TestCase::new(
+                        format!(
+                            "set_{}_{}_u32_u16_{}",
+                            cmp.as_str(),
+                            bool_op.as_str(),
+                            integer_predicate.to_string()
+                        ),
+                        make_range(SetIntTest::<u16, u32>::new(
+                            cmp,
+                            Some((bool_op, integer_predicate)),
+                        )),
+                    )
+                    .with_metadata(
+                        TestMetadata::new("set")
+                            .types(["u32", "u16"])
+                            .modifiers([cmp.as_str(), bool_op.as_str()]),
                     ),
-                    make_range(SetIntTest::<u16, u32>::new(
-                        cmp,
-                        Some((bool_op, integer_predicate)),
-                    )),
-                ));
+                );
                 if !cmp.signed() {
                     continue;
                 }
-                tests.push(TestCase::new(
-                    format!(
-                        "set_{}_{}_u32_s16_{}",
-                        cmp.as_str(),
-                        bool_op.as_str(),
-                        integer_predicate.to_string()
+                tests.push(
+                    TestCase::new(
+                        format!(
+                            "set_{}_{}_u32_s16_{}",
+                            cmp.as_str(),
+                            bool_op.as_str(),
+                            integer_predicate.to_string()
+                        ),
+                        make_range(SetIntTest::<i16, u32>::new(
+                            cmp,
+                            Some((bool_op, integer_predicate)),
+                        )),
+                    )
+                    .with_metadata(
+                        TestMetadata::new("set")
+                            .types(["u32", "s16"])
+                            .modifiers([cmp.as_str(), bool_op.as_str()]),
                     ),
-                    make_range(SetIntTest::<i16, u32>::new(
-                        cmp,
-                        Some((bool_op, integer_predicate)),
-                    )),
-                ));
+                );
             }
         }
     }
@@ -311,19 +340,27 @@ pub fn all_tests() -> Vec<TestCase> {
         for ftz in [true, false] {
             let ftz_text = if ftz { "_ftz" } else { "" };
             for bool_op in [BoolOp::And, BoolOp::Or, BoolOp::Xor] {
-                tests.push(TestCase::new(
-                    format!(
-                        "set_{}_{}{}_f32_f32",
-                        cmp_op.as_str(),
-                        bool_op.as_str(),
-                        ftz_text
+                tests.push(
+                    TestCase::new(
+                        format!(
+                            "set_{}_{}{}_f32_f32",
+                            cmp_op.as_str(),
+                            bool_op.as_str(),
+                            ftz_text
+                        ),
+                        make_random(SetFloatTest {
+                            cmp_op,
+                            bool_op,
+                            ftz,
+                        }),
+                    )
+                    .with_metadata(
+                        TestMetadata::new("set")
+                            .types(["f32", "f32"])
+                            .modifiers([cmp_op.as_str(), bool_op.as_str()])
+                            .flag("ftz", ftz),
                     ),
-                    make_random(SetFloatTest {
-                        cmp_op,
-                        bool_op,
-                        ftz,
-                    }),
-                ));
+                );
             }
         }
     }
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, RandomTest, TestCase, TestCommon, TestPtx};
 
 static PTX: &str = include_str!("shf.ptx");
@@ -34,8 +35,8 @@ impl TestPtx for Shf {
         PTX.replace("<DIR>", dir_str).replace("<MODE>", mode_str)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "input_c", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "input_c", "output"]
     }
 }
 
@@ -97,6 +98,13 @@ pub fn all_tests() -> Vec<TestCase> {
                 dir: ShfDir::Left,
                 mode: ShfMode::Clamp,
             }),
+        )
+        .with_metadata(
+            TestMetadata::new("shf")
+                .types(["b32"])
+                .modifiers(["l", "clamp"])
+                .min_sm(32)
+                .min_ptx(3, 1),
         ),
         TestCase::new(
             "shf_l_wrap_b32".to_string(),
@@ -104,6 +112,13 @@ pub fn all_tests() -> Vec<TestCase> {
                 dir: ShfDir::Left,
                 mode: ShfMode::Wrap,
             }),
+        )
+        .with_metadata(
+            TestMetadata::new("shf")
+                .types(["b32"])
+                .modifiers(["l", "wrap"])
+                .min_sm(32)
+                .min_ptx(3, 1),
         ),
         TestCase::new(
             "shf_r_clamp_b32".to_string(),
@@ -111,6 +126,13 @@ pub fn all_tests() -> Vec<TestCase> {
                 dir: ShfDir::Right,
                 mode: ShfMode::Clamp,
             }),
+        )
+        .with_metadata(
+            TestMetadata::new("shf")
+                .types(["b32"])
+                .modifiers(["r", "clamp"])
+                .min_sm(32)
+                .min_ptx(3, 1),
         ),
         TestCase::new(
             "shf_r_wrap_b32".to_string(),
@@ -118,6 +140,13 @@ pub fn all_tests() -> Vec<TestCase> {
                 dir: ShfDir::Right,
                 mode: ShfMode::Wrap,
             }),
+        )
+        .with_metadata(
+            TestMetadata::new("shf")
+                .types(["b32"])
+                .modifiers(["r", "wrap"])
+                .min_sm(32)
+                .min_ptx(3, 1),
         ),
     ]
 }
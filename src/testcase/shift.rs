@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, PtxScalar, RangeTest, TestCase, TestCommon, TestPtx};
 use num::PrimInt;
 use std::mem;
@@ -9,15 +10,18 @@ pub fn all_tests() -> Vec<TestCase> {
         TestCase::new(
             "shl_b16".to_string(),
             make_range(Shl {}),
-        ),
+        )
+        .with_metadata(TestMetadata::new("shl").types(["b16"])),
         TestCase::new(
             "shr_u16".to_string(),
             make_range::<Shr<u16>>(Shr { _phantom: std::marker::PhantomData }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("shr").types(["u16"])),
         TestCase::new(
             "shr_s16".to_string(),
             make_range::<Shr<i16>>(Shr { _phantom: std::marker::PhantomData }),
-        ),
+        )
+        .with_metadata(TestMetadata::new("shr").types(["s16"])),
     ]
 }
 
@@ -28,8 +32,8 @@ impl TestPtx for Shl {
         PTX.replace("<OP>", "shl.b16")
     }
 
-    fn args(&self) -> &[&str] {
-        &[
+    fn args(&self) -> Vec<&str> {
+        vec![
             "input_a",
             "input_b",
             "output",
@@ -68,8 +72,8 @@ impl<T: PtxScalar> TestPtx for Shr<T> {
         PTX.replace("<OP>", op)
     }
 
-    fn args(&self) -> &[&str] {
-        &[
+    fn args(&self) -> Vec<&str> {
+        vec![
             "input_a",
             "input_b",
             "output",
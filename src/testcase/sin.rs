@@ -1,4 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
 use std::f32;
 
@@ -14,8 +15,12 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn sin(ftz: bool) -> TestCase {
     let test = make_range(Sin { ftz });
+    let metadata = TestMetadata::new("sin")
+        .types(["f32"])
+        .modifiers(["approx"])
+        .flag("ftz", ftz);
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("sin_approx{}", ftz), test)
+    TestCase::new(format!("sin_approx{}", ftz), test).with_metadata(metadata)
 }
 
 struct Sin {
@@ -28,8 +33,8 @@ impl TestPtx for Sin {
         PTX.replace("<FTZ>", &ftz)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
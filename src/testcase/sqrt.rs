@@ -1,8 +1,13 @@
 use crate::common::{self, flush_to_zero_f32, Rounding};
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use std::time::Duration;
 
 static PTX: &str = include_str!("sqrt.ptx");
 
+/// Rounded square roots are verified against MPFR, which makes them the slowest tests of the suite
+const ROUNDED_TIMEOUT: Duration = Duration::from_secs(4 * 60 * 60);
+
 pub fn all_tests() -> Vec<TestCase> {
     let mut tests = vec![];
     for ftz in [false, true] {
@@ -18,7 +23,7 @@ pub fn all_tests() -> Vec<TestCase> {
 }
 
 fn sqrt_rnd(rnd: Rounding, ftz: bool) -> TestCase {
-    sqrt::<false>(rnd, ftz)
+    sqrt::<false>(rnd, ftz).with_timeout(ROUNDED_TIMEOUT)
 }
 
 fn sqrt_approx(ftz: bool) -> TestCase {
@@ -28,8 +33,12 @@ fn sqrt_approx(ftz: bool) -> TestCase {
 fn sqrt<const APPROX: bool>(rnd: Rounding, ftz: bool) -> TestCase {
     let test = make_range::<Sqrt<APPROX>>(Sqrt { rnd, ftz });
     let mode = if APPROX { "approx" } else { rnd.as_str() };
+    let metadata = TestMetadata::new("sqrt")
+        .types(["f32"])
+        .modifiers([mode])
+        .flag("ftz", ftz);
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("sqrt_{}{}", mode, ftz), test)
+    TestCase::new(format!("sqrt_{}{}", mode, ftz), test).with_metadata(metadata)
 }
 
 struct Sqrt<const APPROX: bool> {
@@ -52,8 +61,8 @@ impl<const APPROX: bool> TestPtx for Sqrt<APPROX> {
         PTX.replace("<MODE>", &mode)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
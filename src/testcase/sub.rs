@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, PtxScalar, RangeTest, TestCase, TestCommon, TestPtx};
 use num::cast::AsPrimitive;
 use num::{PrimInt, Saturating};
@@ -32,9 +33,12 @@ where
 
 pub fn all_tests() -> Vec<TestCase> {
     vec![
-        TestCase::new("sub_u16".to_string(), make_range(SubTest::<u16>::default())),
-        TestCase::new("sub_i16".to_string(), make_range(SubTest::<i16>::default())),
-        TestCase::new("sub_sat_s32".to_string(), make_range(SubSatTest::default())),
+        TestCase::new("sub_u16".to_string(), make_range(SubTest::<u16>::default()))
+            .with_metadata(TestMetadata::new("sub").types(["u16"])),
+        TestCase::new("sub_i16".to_string(), make_range(SubTest::<i16>::default()))
+            .with_metadata(TestMetadata::new("sub").types(["s16"])),
+        TestCase::new("sub_sat_s32".to_string(), make_range(SubSatTest::default()))
+            .with_metadata(TestMetadata::new("sub").types(["s32"]).modifiers(["sat"])),
     ]
 }
 
@@ -50,8 +54,8 @@ impl<T: PtxScalar> TestPtx for SubTest<T> {
            .replace("<TYPE_SIZE>", &mem::size_of::<T>().to_string())
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -93,8 +97,8 @@ impl TestPtx for SubSatTest {
            .replace("<TYPE_SIZE>", "4")
     }
 
-    fn args(&self) -> &[&str] {
-        &["input_a", "input_b", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input_a", "input_b", "output"]
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::{
     common,
     test::{make_range, RangeTest, TestCase, TestCommon, TestPtx},
@@ -12,7 +13,13 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn tanh() -> TestCase {
     let test = make_range(Tanh {});
-    TestCase::new("tanh_approx".to_string(), test)
+    TestCase::new("tanh_approx".to_string(), test).with_metadata(
+        TestMetadata::new("tanh")
+            .types(["f32"])
+            .modifiers(["approx"])
+            .min_sm(75)
+            .min_ptx(7, 0),
+    )
 }
 
 pub struct Tanh {}
@@ -24,8 +31,8 @@ impl TestPtx for Tanh {
         PTX.to_string()
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
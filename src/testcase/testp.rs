@@ -1,5 +1,6 @@
 use std::mem;
 use std::marker::PhantomData;
+use crate::metadata::TestMetadata;
 use crate::test::{make_range, PtxScalar, RangeTest, TestCase, TestCommon, TestPtx};
 
 pub static PTX: &str = include_str!("testp.ptx");
@@ -42,6 +43,7 @@ pub fn all_tests() -> Vec<TestCase> {
             format!("testp_{}_f32", mode.as_str()),
             make_range(Testp::<f32>::new(*mode))
         )
+        .with_metadata(TestMetadata::new("testp").types(["f32"]).modifiers([mode.as_str()]))
     }).collect::<Vec<_>>()
 
 }
@@ -67,8 +69,8 @@ impl<T: PtxScalar> TestPtx for Testp<T> {
            .replace("<TYPE_SIZE>", &mem::size_of::<T>().to_string())
     }
 
-    fn args(&self) -> &[&str] {
-        &["input", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input", "output"]
     }
 }
 
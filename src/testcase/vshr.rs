@@ -1,3 +1,4 @@
+use crate::metadata::TestMetadata;
 use crate::test::{make_random, PtxScalar, RandomTest, TestCase, TestCommon, TestPtx};
 use num::{cast::AsPrimitive, traits::WrappingAdd};
 use rand::{distributions::Standard, prelude::Distribution, Rng};
@@ -54,8 +55,8 @@ impl<
             .replace("<OP2_ARGS>", op2_args)
     }
 
-    fn args(&self) -> &[&str] {
-        &["input0", "input1", "input2", "output"]
+    fn args(&self) -> Vec<&str> {
+        vec!["input0", "input1", "input2", "output"]
     }
 }
 
@@ -232,5 +233,13 @@ where
             //Some(SecondaryOp::Max) => "_max",
         },
     );
-    TestCase::new(test_name, make_random(config))
+    let metadata = TestMetadata::new("vshr")
+        .types([D::name(), A::name(), "u32"])
+        .flag("sat", sat)
+        .modifiers([match mode {
+            VshrMode::Clamp => "clamp",
+            VshrMode::Wrap => "wrap",
+        }])
+        .flag("add", op2.is_some());
+    TestCase::new(test_name, make_random(config)).with_metadata(metadata)
 }